@@ -0,0 +1,593 @@
+//! Loading a [`BasicContext`] from a `linkcheck.toml` file.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use linkcheck::config::Config;
+//! use std::path::Path;
+//!
+//! let config = Config::from_toml(
+//!     r#"
+//!     ignore = ["^https://localhost"]
+//!     concurrency = 16
+//!     timeout = 30
+//!     slug-style = "github"
+//!
+//!     [alternate-extensions]
+//!     md = ["html"]
+//!
+//!     [[ignore-rules]]
+//!     file = "CHANGELOG.md"
+//!     reason = "old releases link to pages which no longer exist"
+//!
+//!     [hosts."api.github.com"]
+//!     headers = { Authorization = "token abc123" }
+//!     "#,
+//! )
+//! .unwrap();
+//!
+//! let ctx = config.into_context(Path::new(".")).unwrap();
+//! ```
+//!
+//! Paths in the config file (e.g. `root-directory` and `cache-file`) are
+//! resolved relative to the directory containing it.
+
+use crate::{
+    validation::{
+        GitHubSlugger, GitLabSlugger, HostLimits, IgnoreRule, IgnoreRules,
+        MdbookSlugger, Options, RustdocSlugger, Severity, WarningKind,
+    },
+    BasicContext, LinkKind,
+};
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{de::value::StrDeserializer, Deserialize};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The contents of a `linkcheck.toml` file.
+///
+/// Everything is optional, and anything which isn't mentioned keeps the same
+/// default as [`BasicContext::default()`].
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Regular expressions for `href`s which shouldn't be checked.
+    pub ignore: Vec<String>,
+    /// More detailed rules for links which shouldn't be checked.
+    pub ignore_rules: Vec<IgnoreRuleConfig>,
+    /// If set, only web links to these hosts (and their subdomains) are
+    /// checked.
+    pub allowed_hosts: Option<Vec<String>>,
+    /// Web links to these hosts (and their subdomains) aren't checked.
+    pub denied_hosts: Vec<String>,
+    /// Links starting with a `/` are resolved relative to this directory.
+    pub root_directory: Option<PathBuf>,
+    /// The file to use when a link points to a directory.
+    pub default_file: Option<String>,
+    /// Extra extensions to try when a link's file doesn't exist (e.g.
+    /// `md = ["html"]`).
+    pub alternate_extensions: HashMap<String, Vec<String>>,
+    /// Are links allowed to go outside of the root directory?
+    pub allow_traversal: Option<bool>,
+    /// How headings are turned into anchors.
+    pub slug_style: Option<SlugStyle>,
+    /// Remember the results of web requests in this file.
+    pub cache_file: Option<PathBuf>,
+    /// How many web requests may be in flight at a time, across all hosts.
+    pub concurrency: Option<usize>,
+    /// How long (in seconds) a single web request may take.
+    pub timeout: Option<u64>,
+    /// How long (in seconds) a web request may take before it is reported as
+    /// slow.
+    pub slow_response_threshold: Option<u64>,
+    /// How long (in seconds) a cached result is considered valid.
+    pub cache_timeout: Option<u64>,
+    /// How long (in seconds) a permanently broken link keeps being reported
+    /// as broken before it is checked again.
+    pub negative_cache_timeout: Option<u64>,
+    /// How seriously each kind of warning should be taken, keyed by
+    /// [`WarningKind`] (e.g. `permanent-redirect = "error"`).
+    pub severity: HashMap<String, Severity>,
+    /// Settings for individual hosts, keyed by host name.
+    pub hosts: HashMap<String, HostConfig>,
+}
+
+/// A single rule for ignoring links, written as a `[[ignore-rules]]` table.
+///
+/// Exactly one of `href`, `href-glob`, `host`, `file` or `kind` must be
+/// provided.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct IgnoreRuleConfig {
+    /// A regular expression matching the link's `href`.
+    pub href: Option<String>,
+    /// A glob matching the link's `href`.
+    pub href_glob: Option<String>,
+    /// A host whose links (including subdomains) are ignored.
+    pub host: Option<String>,
+    /// A glob matching the document containing the link, relative to the
+    /// root directory.
+    pub file: Option<String>,
+    /// Ignore every link written a particular way (e.g. `kind = "image"`).
+    pub kind: Option<LinkKind>,
+    /// Why links matching this rule are ignored.
+    pub reason: Option<String>,
+}
+
+/// Settings for a single host.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HostConfig {
+    /// Extra headers to send with each request.
+    pub headers: HashMap<String, String>,
+    /// The maximum number of requests that may be in flight at a time.
+    pub concurrency: Option<usize>,
+    /// The maximum number of requests to start each second, where `0` (or
+    /// `false` in the config file) means there is no limit.
+    #[serde(deserialize_with = "rate_limit")]
+    pub requests_per_second: Option<f64>,
+}
+
+/// The different ways headings can be turned into anchors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SlugStyle {
+    /// The [`MdbookSlugger`].
+    Mdbook,
+    /// The [`GitHubSlugger`].
+    #[serde(rename = "github")]
+    GitHub,
+    /// The [`GitLabSlugger`].
+    #[serde(rename = "gitlab")]
+    GitLab,
+    /// The [`RustdocSlugger`].
+    Rustdoc,
+}
+
+impl Config {
+    /// The name linkcheck's config file normally has.
+    pub const FILE_NAME: &'static str = "linkcheck.toml";
+
+    /// Parse a [`Config`] from TOML.
+    pub fn from_toml(text: &str) -> Result<Config, ConfigError> {
+        let mut deserializer = toml::Deserializer::new(text);
+
+        serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+            ConfigError::Invalid {
+                key: e.path().to_string(),
+                message: e.into_inner().to_string(),
+            }
+        })
+    }
+
+    /// Read a [`Config`] from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|error| ConfigError::Io {
+                path: path.to_path_buf(),
+                error,
+            })?;
+
+        Config::from_toml(&text)
+    }
+
+    /// Read a config file and turn it into a [`BasicContext`], resolving
+    /// paths relative to the directory containing it.
+    pub fn load_context<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<BasicContext, ConfigError> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or_else(|| Path::new("."));
+
+        Config::load(path)?.into_context(directory)
+    }
+
+    /// Create a ready-to-use [`BasicContext`], resolving any relative paths
+    /// against `directory`.
+    pub fn into_context(
+        self,
+        directory: &Path,
+    ) -> Result<BasicContext, ConfigError> {
+        let mut builder = BasicContext::client_builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+        let client = builder
+            .build()
+            .map_err(|e| ConfigError::invalid("timeout", e))?;
+
+        let mut ctx = BasicContext::with_client(client)
+            .set_ignore_rules(self.ignore_rules()?);
+        ctx.options = self.options(directory)?;
+
+        if let Some(cache_file) = self.cache_file {
+            ctx = ctx
+                .set_cache_file(directory.join(cache_file))
+                .map_err(|e| ConfigError::invalid("cache-file", e))?;
+        }
+
+        if let Some(concurrency) = self.concurrency {
+            if concurrency == 0 {
+                return Err(ConfigError::invalid(
+                    "concurrency",
+                    "must be at least 1",
+                ));
+            }
+            ctx = ctx.set_concurrency(concurrency);
+        }
+
+        if let Some(threshold) = self.slow_response_threshold {
+            ctx =
+                ctx.set_slow_response_threshold(Duration::from_secs(threshold));
+        }
+        if let Some(timeout) = self.cache_timeout {
+            ctx = ctx.set_cache_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = self.negative_cache_timeout {
+            ctx = ctx.set_negative_cache_timeout(Duration::from_secs(timeout));
+        }
+
+        for (name, severity) in self.severity {
+            let kind = warning_kind(&name).map_err(|e| {
+                ConfigError::invalid(format!("severity.{}", name), e)
+            })?;
+            ctx = ctx.set_severity(kind, severity);
+        }
+
+        for (host, config) in self.hosts {
+            let key = format!("hosts.{}", host);
+
+            if !config.headers.is_empty() {
+                let headers = config.header_map(&key)?;
+                ctx = ctx.set_host_headers(host.clone(), headers);
+            }
+
+            if config.concurrency.is_some()
+                || config.requests_per_second.is_some()
+            {
+                let defaults = HostLimits::default();
+                let limits = HostLimits {
+                    concurrency: config
+                        .concurrency
+                        .unwrap_or(defaults.concurrency),
+                    requests_per_second: match config.requests_per_second {
+                        Some(rps) if rps <= 0.0 => None,
+                        Some(rps) => Some(rps),
+                        None => defaults.requests_per_second,
+                    },
+                };
+                ctx = ctx.set_host_limits(host, limits);
+            }
+        }
+
+        Ok(ctx)
+    }
+
+    fn ignore_rules(&self) -> Result<IgnoreRules, ConfigError> {
+        let mut rules = IgnoreRules::new();
+
+        for (i, pattern) in self.ignore.iter().enumerate() {
+            let pattern = Regex::new(pattern).map_err(|e| {
+                ConfigError::invalid(format!("ignore[{}]", i), e)
+            })?;
+            rules = rules.add_rule(IgnoreRule::href(pattern));
+        }
+
+        for (i, rule) in self.ignore_rules.iter().enumerate() {
+            rules =
+                rules.add_rule(rule.to_rule(&format!("ignore-rules[{}]", i))?);
+        }
+
+        for host in &self.denied_hosts {
+            rules = rules.deny_host(host.as_str());
+        }
+
+        for host in self.allowed_hosts.iter().flatten() {
+            rules = rules.allow_host(host.as_str());
+        }
+
+        Ok(rules)
+    }
+
+    fn options(&self, directory: &Path) -> Result<Options, ConfigError> {
+        let mut options = Options::default();
+
+        if let Some(ref root) = self.root_directory {
+            options = options
+                .with_root_directory(directory.join(root))
+                .map_err(|e| ConfigError::invalid("root-directory", e))?;
+        }
+
+        if let Some(ref default_file) = self.default_file {
+            options = options.set_default_file(default_file);
+        }
+
+        if !self.alternate_extensions.is_empty() {
+            options = options
+                .set_alternate_extensions(self.alternate_extensions.clone());
+        }
+
+        if let Some(allow_traversal) = self.allow_traversal {
+            options = options
+                .set_links_may_traverse_the_root_directory(allow_traversal);
+        }
+
+        options = match self.slug_style {
+            Some(SlugStyle::Mdbook) => options.set_slugger(MdbookSlugger),
+            Some(SlugStyle::GitHub) => options.set_slugger(GitHubSlugger),
+            Some(SlugStyle::GitLab) => options.set_slugger(GitLabSlugger),
+            Some(SlugStyle::Rustdoc) => options.set_slugger(RustdocSlugger),
+            None => options,
+        };
+
+        Ok(options)
+    }
+}
+
+/// Accept a number of requests per second, or `false` to turn the rate limit
+/// off (the same as `0`).
+fn rate_limit<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RateLimit {
+        PerSecond(f64),
+        Enabled(bool),
+    }
+
+    match RateLimit::deserialize(deserializer)? {
+        RateLimit::PerSecond(rps) if rps >= 0.0 => Ok(Some(rps)),
+        RateLimit::Enabled(false) => Ok(Some(0.0)),
+        _ => Err(serde::de::Error::custom(
+            "expected a number of requests per second, or false for no limit",
+        )),
+    }
+}
+
+fn warning_kind(name: &str) -> Result<WarningKind, serde::de::value::Error> {
+    WarningKind::deserialize(StrDeserializer::new(name))
+}
+
+impl IgnoreRuleConfig {
+    fn to_rule(&self, key: &str) -> Result<IgnoreRule, ConfigError> {
+        let invalid = |field: &str, e: &dyn ToString| {
+            ConfigError::invalid(format!("{}.{}", key, field), e.to_string())
+        };
+
+        let mut rules = Vec::new();
+
+        if let Some(ref href) = self.href {
+            let href = Regex::new(href).map_err(|e| invalid("href", &e))?;
+            rules.push(IgnoreRule::href(href));
+        }
+        if let Some(ref glob) = self.href_glob {
+            rules.push(
+                IgnoreRule::href_glob(glob)
+                    .map_err(|e| invalid("href-glob", &e))?,
+            );
+        }
+        if let Some(ref host) = self.host {
+            rules.push(IgnoreRule::host(host.as_str()));
+        }
+        if let Some(ref glob) = self.file {
+            rules.push(
+                IgnoreRule::file_glob(glob).map_err(|e| invalid("file", &e))?,
+            );
+        }
+        if let Some(kind) = self.kind {
+            rules.push(IgnoreRule::kind(kind));
+        }
+
+        if rules.len() != 1 {
+            return Err(ConfigError::invalid(
+                key,
+                "expected exactly one of \"href\", \"href-glob\", \"host\", \
+                 \"file\" or \"kind\"",
+            ));
+        }
+        let rule = rules.remove(0);
+
+        Ok(match self.reason {
+            Some(ref reason) => rule.with_reason(reason.as_str()),
+            None => rule,
+        })
+    }
+}
+
+impl HostConfig {
+    fn header_map(&self, key: &str) -> Result<HeaderMap, ConfigError> {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in &self.headers {
+            let key = format!("{}.headers.{}", key, name);
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| ConfigError::invalid(key.clone(), e))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| ConfigError::invalid(key, e))?;
+            headers.insert(name, value);
+        }
+
+        Ok(headers)
+    }
+}
+
+/// An error that may occur while loading a [`Config`].
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The config file couldn't be read.
+    #[error("Unable to read \"{}\"", path.display())]
+    Io {
+        /// The config file.
+        path: PathBuf,
+        /// The underlying error.
+        #[source]
+        error: io::Error,
+    },
+    /// A setting was missing or had an invalid value.
+    #[error("Invalid value for \"{key}\": {message}")]
+    Invalid {
+        /// The offending key (e.g. `hosts."example.com".concurrency`).
+        key: String,
+        /// What was wrong with it.
+        message: String,
+    },
+}
+
+impl ConfigError {
+    fn invalid<K, M>(key: K, message: M) -> Self
+    where
+        K: Into<String>,
+        M: ToString,
+    {
+        ConfigError::Invalid {
+            key: key.into(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The config key this error is about, if there is one.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            ConfigError::Invalid { key, .. } => Some(key),
+            ConfigError::Io { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::link, validation::Context};
+
+    #[test]
+    fn build_a_context() {
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::create_dir(temp.join("docs")).unwrap();
+        let src = r#"
+            ignore = ["^https://localhost"]
+            denied-hosts = ["twitter.com"]
+            root-directory = "docs"
+            concurrency = 16
+            cache-timeout = 60
+
+            [severity]
+            permanent-redirect = "error"
+
+            [[ignore-rules]]
+            file = "vendor/**"
+            reason = "not our links"
+
+            [hosts."example.com"]
+            headers = { Authorization = "token abc123" }
+            requests-per-second = 2.5
+        "#;
+        std::fs::write(temp.join(Config::FILE_NAME), src).unwrap();
+
+        let ctx = Config::load_context(temp.join(Config::FILE_NAME)).unwrap();
+
+        assert_eq!(
+            ctx.filesystem_options().root_directory(),
+            Some(temp.join("docs").as_path())
+        );
+        assert_eq!(ctx.concurrency(), 16);
+        assert_eq!(ctx.cache_timeout(), Duration::from_secs(60));
+        assert!(ctx.should_ignore(&link("https://localhost:8000/")));
+        assert!(!ctx.should_ignore(&link("https://example.com/")));
+        assert!(ctx.should_ignore(&link("https://twitter.com/")));
+        assert_eq!(
+            ctx.ignore_reason(
+                &link("./README.md"),
+                &temp.join("docs").join("vendor").join("README.md")
+            )
+            .as_deref(),
+            Some("not our links")
+        );
+        let url = "https://example.com/".parse().unwrap();
+        assert_eq!(
+            ctx.url_specific_headers(&url)["Authorization"],
+            "token abc123"
+        );
+        assert_eq!(
+            ctx.host_limits("example.com").requests_per_second,
+            Some(2.5)
+        );
+    }
+
+    #[test]
+    fn rate_limits_can_be_turned_off() {
+        for value in &["0", "false"] {
+            let src = format!(
+                "[hosts.\"example.com\"]\nrequests-per-second = {}",
+                value
+            );
+
+            let ctx = Config::from_toml(&src)
+                .unwrap()
+                .into_context(Path::new("."))
+                .unwrap();
+
+            let limits = ctx.host_limits("example.com");
+            assert_eq!(limits.requests_per_second, None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn parse_every_slug_style() {
+        let inputs = vec![
+            ("mdbook", SlugStyle::Mdbook),
+            ("github", SlugStyle::GitHub),
+            ("gitlab", SlugStyle::GitLab),
+            ("rustdoc", SlugStyle::Rustdoc),
+        ];
+
+        for (name, style) in inputs {
+            let src = format!("slug-style = \"{}\"", name);
+
+            let config = Config::from_toml(&src).unwrap();
+
+            assert_eq!(config.slug_style, Some(style));
+        }
+    }
+
+    #[test]
+    fn errors_mention_the_offending_key() {
+        let inputs = vec![
+            ("concurrency = \"lots\"", "concurrency"),
+            ("ignore = [\"ok\", \"(\"]", "ignore[1]"),
+            ("[[ignore-rules]]\nreason = \"x\"", "ignore-rules[0]"),
+            ("[[ignore-rules]]\nkind = \"video\"", "ignore-rules[0].kind"),
+            ("[[ignore-rules]]\nfile = \"[\"", "ignore-rules[0].file"),
+            ("concurrency = 0", "concurrency"),
+            ("slug-style = \"markdown\"", "slug-style"),
+            (
+                "[severity]\nbroken-link = \"error\"",
+                "severity.broken-link",
+            ),
+            (
+                "[hosts.\"example.com\"]\nrequests-per-second = \"fast\"",
+                "hosts.example.com.requests-per-second",
+            ),
+            (
+                "[hosts.\"example.com\".headers]\n\"Bad Header\" = \"x\"",
+                "hosts.example.com.headers.Bad Header",
+            ),
+        ];
+
+        for (src, key) in inputs {
+            let err = Config::from_toml(src)
+                .and_then(|config| config.into_context(Path::new(".")))
+                .unwrap_err();
+
+            assert_eq!(err.key(), Some(key), "{}", err);
+        }
+    }
+}
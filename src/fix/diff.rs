@@ -0,0 +1,79 @@
+//! A minimal unified diff, good enough for showing which lines an
+//! [`crate::fix::Edit`] would change.
+
+use std::fmt::Write;
+
+/// Compare two versions of a document line by line.
+///
+/// Fixing a link never adds or removes lines, so lines are compared in
+/// pairs and each changed line gets its own hunk (without any context).
+pub(crate) fn unified(name: &str, before: &str, after: &str) -> String {
+    let mut diff = String::new();
+
+    if before == after {
+        return diff;
+    }
+
+    writeln!(diff, "--- {}", name).unwrap();
+    writeln!(diff, "+++ {}", name).unwrap();
+
+    let mut before_lines = before.lines();
+    let mut after_lines = after.lines();
+    let mut line = 1;
+
+    loop {
+        match (before_lines.next(), after_lines.next()) {
+            (None, None) => break,
+            (old, new) if old == new => {},
+            (old, new) => {
+                let old_count = if old.is_some() { 1 } else { 0 };
+                let new_count = if new.is_some() { 1 } else { 0 };
+                writeln!(
+                    diff,
+                    "@@ -{},{} +{},{} @@",
+                    line, old_count, line, new_count
+                )
+                .unwrap();
+                if let Some(old) = old {
+                    writeln!(diff, "-{}", old).unwrap();
+                }
+                if let Some(new) = new {
+                    writeln!(diff, "+{}", new).unwrap();
+                }
+            },
+        }
+
+        line += 1;
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_lines_get_a_hunk() {
+        let before = "# Title\n\n[a](http://a.com/)\nunchanged\n[b](#Usage)\n";
+        let after = "# Title\n\n[a](https://a.com/)\nunchanged\n[b](#usage)\n";
+
+        let got = unified("README.md", before, after);
+
+        let should_be = "--- README.md
++++ README.md
+@@ -3,1 +3,1 @@
+-[a](http://a.com/)
++[a](https://a.com/)
+@@ -5,1 +5,1 @@
+-[b](#Usage)
++[b](#usage)
+";
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn identical_documents_have_an_empty_diff() {
+        assert_eq!(unified("README.md", "same\n", "same\n"), "");
+    }
+}
@@ -0,0 +1,671 @@
+//! Automatically fixing links which are broken or have a
+//! [`crate::validation::Warning`] attached.
+//!
+//! The [`Outcomes`] of validation already know where each [`Link`] lives, so
+//! [`fixes()`] can work out a replacement `href` and turn it into an [`Edit`]
+//! against the original document. Nothing is written to disk, so you can
+//! [`FileFix::apply()`] the edits yourself or look at a [`FileFix::diff()`]
+//! first.
+//!
+//! At the moment we know how to fix:
+//!
+//! - Permanent redirects, by linking straight to where the link moved
+//! - `http://` links, when the `https://` version also works
+//! - Fragments which don't match any anchor, when there is a suggested
+//!   replacement (see [`crate::validation::InvalidLink::suggestions`])
+//!
+//! Suggestions which point at a different file are never applied, because
+//! a similar name isn't necessarily the file the author meant.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use codespan::Files;
+//! use linkcheck::{fix, validation::Outcomes, BasicContext};
+//!
+//! # async fn run(
+//! #     files: Files<String>,
+//! #     outcomes: Outcomes,
+//! # ) -> Result<(), fix::EditError> {
+//! let ctx = BasicContext::default();
+//!
+//! let fixes = fix::fixes(&files, &outcomes, &ctx).await;
+//! print!("{}", fix::diff(&files, &fixes)?);
+//! # Ok(())
+//! # }
+//! ```
+
+mod diff;
+
+use crate::{
+    scanners::find_definition,
+    validation::{
+        check_web_in_session, escalate_warnings, with_fragment, Context,
+        LinkWarning, Outcomes, Reason, Session, Warning,
+    },
+    Link, LinkKind,
+};
+use codespan::{FileId, Files, Span};
+use futures::{stream::FuturesUnordered, StreamExt};
+use reqwest::Url;
+use std::collections::BTreeMap;
+
+/// Why a [`Link`] is being rewritten.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum FixKind {
+    /// The link was permanently redirected, so it now points at the final
+    /// destination.
+    PermanentRedirect,
+    /// The link used `http://`, but the `https://` version also works.
+    UpgradeToHttps,
+    /// The fragment didn't match an anchor exactly, so it was replaced with
+    /// the closest anchor that exists.
+    Fragment,
+}
+
+/// A single change to a document.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edit {
+    /// The text being replaced.
+    pub span: Span,
+    /// The link's original `href`.
+    pub original: String,
+    /// What to replace it with.
+    pub replacement: String,
+    /// Why the link is being changed.
+    pub kind: FixKind,
+}
+
+/// Every [`Edit`] for a single document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileFix {
+    /// The document being edited.
+    pub file: FileId,
+    /// The changes to make, in order and without any overlaps.
+    pub edits: Vec<Edit>,
+}
+
+impl FileFix {
+    /// Apply each [`Edit`] to the document's source text.
+    ///
+    /// The edits must be in order and mustn't overlap, otherwise there is no
+    /// way to tell which change should win.
+    pub fn apply(&self, source: &str) -> Result<String, EditError> {
+        let mut fixed = String::with_capacity(source.len());
+        let mut cursor = 0;
+        let mut previous: Option<Span> = None;
+
+        for edit in &self.edits {
+            let start = edit.span.start().to_usize();
+            let end = edit.span.end().to_usize();
+
+            if let Some(previous) = previous.filter(|_| start < cursor) {
+                return Err(EditError::Overlapping {
+                    first: previous,
+                    second: edit.span,
+                });
+            }
+            if source.get(start..end).is_none() {
+                return Err(EditError::OutOfBounds { span: edit.span });
+            }
+
+            fixed.push_str(&source[cursor..start]);
+            fixed.push_str(&edit.replacement);
+            cursor = end;
+            previous = Some(edit.span);
+        }
+
+        fixed.push_str(&source[cursor..]);
+        Ok(fixed)
+    }
+
+    /// Show what [`FileFix::apply()`] would do as a unified diff, without
+    /// changing anything.
+    pub fn diff(&self, name: &str, source: &str) -> Result<String, EditError> {
+        self.apply(source)
+            .map(|fixed| diff::unified(name, source, &fixed))
+    }
+}
+
+/// Why a [`FileFix`] couldn't be applied.
+#[derive(Debug, Copy, Clone, PartialEq, thiserror::Error)]
+#[non_exhaustive]
+pub enum EditError {
+    /// Two [`Edit`]s try to change the same text, or are out of order.
+    #[error("The edit at {second} overlaps the edit at {first}")]
+    Overlapping {
+        /// The [`Edit`] which was applied first.
+        first: Span,
+        /// The [`Edit`] which overlaps it.
+        second: Span,
+    },
+    /// An [`Edit`] doesn't lie on character boundaries inside the document.
+    #[error("The edit at {span} doesn't fit inside the document")]
+    OutOfBounds {
+        /// Where the [`Edit`] is.
+        span: Span,
+    },
+}
+
+/// Work out how to fix every link in the [`Outcomes`] which we know how to
+/// fix.
+///
+/// Broken links are replaced with their best
+/// [`crate::validation::InvalidLink::suggestions`], and the `https://`
+/// version of a link is checked before suggesting it. Those checks share
+/// the [`Context::concurrency()`] and [`Context::host_limits()`], and run
+/// concurrently.
+pub async fn fixes<S, C>(
+    files: &Files<S>,
+    outcomes: &Outcomes,
+    ctx: &C,
+) -> Vec<FileFix>
+where
+    S: AsRef<str>,
+    C: Context + ?Sized,
+{
+    let mut edits: BTreeMap<FileId, Vec<Edit>> = BTreeMap::new();
+
+    let warnings = outcomes.warnings.iter().map(
+        |LinkWarning { link, warning, .. }| (link, Problem::Warning(warning)),
+    );
+    let invalid = outcomes.invalid.iter().map(|invalid| {
+        let problem = match &invalid.reason {
+            Reason::Warning(warning) => Problem::Warning(warning),
+            _ => Problem::Broken(&invalid.suggestions),
+        };
+        (&invalid.link, problem)
+    });
+
+    let session = Session::new(ctx);
+    let session = &session;
+    let fixed: Vec<_> = warnings
+        .chain(invalid)
+        .map(|(link, problem)| async move {
+            (link, fix(link, problem, ctx, session).await)
+        })
+        .collect::<FuturesUnordered<_>>()
+        .collect()
+        .await;
+
+    for (link, fixed) in fixed {
+        let (replacement, kind) = match fixed {
+            Some(fix) => fix,
+            None => continue,
+        };
+
+        match edit(files, link, replacement, kind) {
+            Some(edit) => edits.entry(link.file).or_default().push(edit),
+            None => log::debug!(
+                "Unable to find \"{}\" in its document, so it can't be fixed",
+                link.href
+            ),
+        }
+    }
+
+    edits
+        .into_iter()
+        .map(|(file, mut edits)| {
+            // a link can have several problems (and links can share a
+            // reference definition), but only one fix per href makes sense
+            // so we keep the most important one
+            edits.sort_by_key(|edit| (edit.span.start(), edit.kind));
+            let mut end = 0;
+            edits.retain(|edit| {
+                let keep = edit.span.start().to_usize() >= end;
+                if keep {
+                    end = edit.span.end().to_usize();
+                }
+                keep
+            });
+            FileFix { file, edits }
+        })
+        .collect()
+}
+
+/// Show every [`FileFix`] as a unified diff.
+pub fn diff<S: AsRef<str>>(
+    files: &Files<S>,
+    fixes: &[FileFix],
+) -> Result<String, EditError> {
+    fixes
+        .iter()
+        .map(|fix| {
+            let name = files.name(fix.file).to_string_lossy();
+            fix.diff(&name, files.source(fix.file).as_ref())
+        })
+        .collect()
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Problem<'a> {
+    Warning(&'a Warning),
+    Broken(&'a [String]),
+}
+
+/// Work out what a [`Link`]'s `href` should be replaced with.
+async fn fix<C>(
+    link: &Link,
+    problem: Problem<'_>,
+    ctx: &C,
+    session: &Session,
+) -> Option<(String, FixKind)>
+where
+    C: Context + ?Sized,
+{
+    match problem {
+        Problem::Warning(Warning::PermanentRedirect { final_url, .. }) => {
+            let mut replacement = final_url.clone();
+            if replacement.fragment().is_none() {
+                replacement.set_fragment(fragment(&link.href));
+            }
+            Some((replacement.to_string(), FixKind::PermanentRedirect))
+        },
+        Problem::Warning(Warning::InsecureLink) => {
+            let replacement = upgrade_to_https(&link.href)?;
+            let url: Url = replacement.parse().ok()?;
+            let checked = check_web_in_session(&url, ctx, session)
+                .await
+                .and_then(|(warnings, _)| {
+                    escalate_warnings(url.as_str(), warnings, ctx)
+                });
+
+            match checked {
+                Ok(()) => Some((replacement, FixKind::UpgradeToHttps)),
+                Err(reason) => {
+                    log::debug!(
+                        "Not upgrading \"{}\" because \"{}\" is broken ({})",
+                        link.href,
+                        replacement,
+                        reason
+                    );
+                    None
+                },
+            }
+        },
+        Problem::Warning(Warning::CaseInsensitiveFragment { anchor }) => {
+            Some((with_fragment(&link.href, anchor), FixKind::Fragment))
+        },
+        Problem::Broken(suggestions) => {
+            let replacement = suggestions.first()?;
+            if without_fragment(replacement) == without_fragment(&link.href) {
+                Some((replacement.clone(), FixKind::Fragment))
+            } else {
+                log::debug!(
+                    "Not replacing \"{}\" with \"{}\" because it points at \
+                     a different file",
+                    link.href,
+                    replacement
+                );
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Create an [`Edit`] which replaces a [`Link`]'s `href` where it was
+/// written.
+///
+/// We give up when the destination can't be found exactly as the `href`
+/// (e.g. because it uses backslash escapes) or could be in more than one
+/// place, because editing the wrong text is worse than not fixing the link.
+fn edit<S: AsRef<str>>(
+    files: &Files<S>,
+    link: &Link,
+    replacement: String,
+    kind: FixKind,
+) -> Option<Edit> {
+    let source = files.source(link.file).as_ref();
+    let start = link.span.start().to_usize();
+    let text = source.get(start..link.span.end().to_usize())?;
+
+    if link.kind == Some(LinkKind::Html) {
+        // the span is the attribute's whole value, which may use character
+        // references
+        return Some(Edit {
+            span: link.span,
+            original: link.href.clone(),
+            replacement: escape_attribute(&replacement),
+            kind,
+        });
+    }
+
+    let start = if text == link.href {
+        // plain text URLs
+        start
+    } else if text.strip_prefix('<').and_then(|t| t.strip_suffix('>'))
+        == Some(link.href.as_str())
+    {
+        // autolinks, e.g. "<https://example.com/>"
+        start + 1
+    } else if let Some(label) = &link.reference {
+        // the href is in the reference definition, not the link
+        let definition = find_definition(source, label)?;
+        let offset =
+            find_destination(&source[definition.clone()], &link.href, "]:")?;
+        definition.start + offset
+    } else {
+        start + find_destination(text, &link.href, "](")?
+    };
+    let span = Span::new(start as u32, (start + link.href.len()) as u32);
+
+    Some(Edit {
+        span,
+        original: link.href.clone(),
+        replacement,
+        kind,
+    })
+}
+
+/// Find the only place `href` is written directly after `prefix` (ignoring
+/// whitespace and the "<" in "<destination>"), so a link's text or title
+/// which happens to repeat the `href` isn't mistaken for its destination.
+fn find_destination(text: &str, href: &str, prefix: &str) -> Option<usize> {
+    let mut candidates = text.match_indices(href).filter_map(|(index, _)| {
+        let before = &text[..index];
+        let before = before.strip_suffix('<').unwrap_or(before).trim_end();
+        if before.ends_with(prefix) {
+            Some(index)
+        } else {
+            None
+        }
+    });
+
+    let first = candidates.next()?;
+    if candidates.next().is_some() {
+        None
+    } else {
+        Some(first)
+    }
+}
+
+/// Write an `href` so it can go inside a quoted HTML attribute.
+fn escape_attribute(href: &str) -> String {
+    href.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn fragment(href: &str) -> Option<&str> {
+    href.find('#').map(|hash| &href[hash + 1..])
+}
+
+fn without_fragment(href: &str) -> &str {
+    match href.find('#') {
+        Some(hash) => &href[..hash],
+        None => href,
+    }
+}
+
+/// Switch an `http://` link to `https://`, keeping the rest of the `href`
+/// exactly as it was written.
+fn upgrade_to_https(href: &str) -> Option<String> {
+    let scheme = href.get(..7)?;
+
+    if scheme.eq_ignore_ascii_case("http://") {
+        Some(format!("https://{}", &href[7..]))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        validation::{InvalidLink, Redirect},
+        BasicContext,
+    };
+
+    fn outcomes_with_warning(link: Link, warning: Warning) -> Outcomes {
+        Outcomes {
+            warnings: vec![LinkWarning {
+                link,
+                warning,
+                web_check: None,
+            }],
+            ..Outcomes::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn permanent_redirects_link_to_their_destination() {
+        let mut files = Files::new();
+        let src = "See [the docs](http://example.com/old#intro) for more.";
+        let file = files.add("README.md", src);
+        let link = Link::new(
+            "http://example.com/old#intro",
+            Span::new(4, 44),
+            file,
+            "README.md".into(),
+        );
+        let final_url: reqwest::Url =
+            "https://example.com/new".parse().unwrap();
+        let warning = Warning::PermanentRedirect {
+            final_url: final_url.clone(),
+            redirects: vec![Redirect {
+                from: "http://example.com/old".parse().unwrap(),
+                to: final_url,
+                status: 301,
+            }],
+        };
+        let outcomes = outcomes_with_warning(link, warning);
+        let ctx = BasicContext::default();
+
+        let got = fixes(&files, &outcomes, &ctx).await;
+
+        assert_eq!(got.len(), 1);
+        assert_eq!(
+            got[0].edits,
+            vec![Edit {
+                span: Span::new(15, 43),
+                original: String::from("http://example.com/old#intro"),
+                replacement: String::from("https://example.com/new#intro"),
+                kind: FixKind::PermanentRedirect,
+            }]
+        );
+        assert_eq!(
+            got[0].apply(src).unwrap(),
+            "See [the docs](https://example.com/new#intro) for more."
+        );
+    }
+
+    #[tokio::test]
+    async fn broken_links_use_their_suggestion() {
+        let mut files = Files::new();
+        let src =
+            "[start](guide.md#getting-startd)\n[top](#Title)\n\n# Title\n";
+        let file = files.add("README.md", src);
+        let link =
+            |href: &str, span| Link::new(href, span, file, "README.md".into());
+        let outcomes = Outcomes {
+            invalid: vec![InvalidLink {
+                link: link("guide.md#getting-startd", Span::new(0, 32)),
+                reason: Reason::File,
+                suggestions: vec![String::from("guide.md#getting-started")],
+            }],
+            warnings: vec![LinkWarning {
+                link: link("#Title", Span::new(33, 46)),
+                warning: Warning::CaseInsensitiveFragment {
+                    anchor: String::from("title"),
+                },
+                web_check: None,
+            }],
+            ..Outcomes::default()
+        };
+        let ctx = BasicContext::default();
+
+        let got = fixes(&files, &outcomes, &ctx).await;
+
+        assert_eq!(
+            got[0].apply(src).unwrap(),
+            "[start](guide.md#getting-started)\n[top](#title)\n\n# Title\n"
+        );
+        assert!(got[0].edits.iter().all(|e| e.kind == FixKind::Fragment));
+    }
+
+    fn redirect(from: &str, to: &str) -> Warning {
+        let final_url: reqwest::Url = to.parse().unwrap();
+        Warning::PermanentRedirect {
+            final_url: final_url.clone(),
+            redirects: vec![Redirect {
+                from: from.parse().unwrap(),
+                to: final_url,
+                status: 301,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn never_edit_the_link_text_or_title() {
+        let mut files = Files::new();
+        let src = "[http://a.com/](http://a.com/ \"http://a.com/\")";
+        let file = files.add("README.md", src);
+        let link = Link::new(
+            "http://a.com/",
+            Span::new(0, 46),
+            file,
+            "README.md".into(),
+        );
+        let outcomes = outcomes_with_warning(
+            link,
+            redirect("http://a.com/", "https://b.com/"),
+        );
+        let ctx = BasicContext::default();
+
+        let got = fixes(&files, &outcomes, &ctx).await;
+
+        assert_eq!(
+            got[0].apply(src).unwrap(),
+            "[http://a.com/](https://b.com/ \"http://a.com/\")"
+        );
+    }
+
+    #[tokio::test]
+    async fn reference_links_are_fixed_in_their_definition() {
+        let mut files = Files::new();
+        let src = "See [the docs][docs].\n\n[Docs]: <http://a.com/> \"Docs\"\n";
+        let file = files.add("README.md", src);
+        let mut link = Link::new(
+            "http://a.com/",
+            Span::new(4, 20),
+            file,
+            "README.md".into(),
+        );
+        link.kind = Some(LinkKind::Reference);
+        link.reference = Some(String::from("docs"));
+        let outcomes = outcomes_with_warning(
+            link,
+            redirect("http://a.com/", "https://b.com/"),
+        );
+        let ctx = BasicContext::default();
+
+        let got = fixes(&files, &outcomes, &ctx).await;
+
+        assert_eq!(
+            got[0].apply(src).unwrap(),
+            "See [the docs][docs].\n\n[Docs]: <https://b.com/> \"Docs\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn html_attributes_can_use_character_references() {
+        let mut files = Files::new();
+        let src = "<a href=\"http://a.com/?x=1&amp;y=2\">A</a>";
+        let file = files.add("index.html", src);
+        let mut link = Link::new(
+            "http://a.com/?x=1&y=2",
+            Span::new(9, 34),
+            file,
+            "index.html".into(),
+        );
+        link.kind = Some(LinkKind::Html);
+        let outcomes = outcomes_with_warning(
+            link,
+            redirect("http://a.com/?x=1&y=2", "https://b.com/?x=1&y=2"),
+        );
+        let ctx = BasicContext::default();
+
+        let got = fixes(&files, &outcomes, &ctx).await;
+
+        assert_eq!(
+            got[0].apply(src).unwrap(),
+            "<a href=\"https://b.com/?x=1&amp;y=2\">A</a>"
+        );
+    }
+
+    #[tokio::test]
+    async fn suggestions_for_other_files_are_not_applied() {
+        let mut files = Files::new();
+        let src = "[guide](gide.md)";
+        let file = files.add("README.md", src);
+        let outcomes = Outcomes {
+            invalid: vec![InvalidLink {
+                link: Link::new(
+                    "gide.md",
+                    Span::new(0, 16),
+                    file,
+                    "README.md".into(),
+                ),
+                reason: Reason::File,
+                suggestions: vec![String::from("guide.md")],
+            }],
+            ..Outcomes::default()
+        };
+        let ctx = BasicContext::default();
+
+        let got = fixes(&files, &outcomes, &ctx).await;
+
+        assert!(got.is_empty());
+    }
+
+    #[test]
+    fn overlapping_edits_are_an_error() {
+        let edit = |start, end| Edit {
+            span: Span::new(start, end),
+            original: String::new(),
+            replacement: String::from("x"),
+            kind: FixKind::Fragment,
+        };
+        let fix = FileFix {
+            file: Files::<String>::new().add("README.md", String::new()),
+            edits: vec![edit(0, 4), edit(2, 6)],
+        };
+
+        assert_eq!(
+            fix.apply("abcdefgh"),
+            Err(EditError::Overlapping {
+                first: Span::new(0, 4),
+                second: Span::new(2, 6),
+            })
+        );
+        assert_eq!(
+            FileFix {
+                edits: vec![edit(4, 20)],
+                ..fix
+            }
+            .apply("abcdefgh"),
+            Err(EditError::OutOfBounds {
+                span: Span::new(4, 20)
+            })
+        );
+    }
+
+    #[test]
+    fn only_http_links_are_upgraded() {
+        assert_eq!(
+            upgrade_to_https("HTTP://example.com/?q=1").as_deref(),
+            Some("https://example.com/?q=1")
+        );
+        assert_eq!(upgrade_to_https("https://example.com/"), None);
+        assert_eq!(upgrade_to_https("./README.md"), None);
+    }
+}
@@ -60,6 +60,10 @@
 //!
 //! * **serde-1** - Adds `Serialize` and `Deserialize` implementations for use
 //!   with `serde`
+//! * **config** - Adds `config::Config`, for loading a [`BasicContext`] from a
+//!   `linkcheck.toml` file
+//! * **cli** - Builds the `linkcheck` command-line tool, which checks every
+//!   markdown and HTML file in a directory tree (implies **config**)
 
 #![forbid(unsafe_code)]
 #![deny(
@@ -72,9 +76,14 @@
 #[macro_use]
 extern crate pretty_assertions;
 
+#[cfg(feature = "config")]
+pub mod config;
+pub mod fix;
+pub mod reporting;
 pub mod scanners;
 pub mod validation;
 
+pub use scanners::LinkKind;
 pub use validation::{validate, BasicContext};
 
 use codespan::{FileId, Span};
@@ -103,9 +112,7 @@ impl Category {
             return None;
         }
 
-        let mailto_prefix = "mailto:";
-        if src.starts_with(mailto_prefix) {
-            let address = &src[mailto_prefix.len()..];
+        if let Some(address) = src.strip_prefix("mailto:") {
             return Some(Category::MailTo(address.to_string()));
         }
 
@@ -113,17 +120,17 @@ impl Category {
             return Some(Category::Url(url));
         }
 
-        if src.starts_with("#") {
+        if let Some(fragment) = src.strip_prefix('#') {
             return Some(Category::CurrentFile {
-                fragment: String::from(&src[1..]),
+                fragment: String::from(fragment),
             });
         }
 
-        let (path, fragment) = match src.find("#") {
+        let (path, fragment) = match src.find('#') {
             Some(hash) => {
                 let (path, rest) = src.split_at(hash);
                 (path, Some(String::from(&rest[1..])))
-            }
+            },
             None => (src, None),
         };
 
@@ -153,6 +160,23 @@ pub struct Link {
     pub file: FileId,
     /// Which document does this [`Link`] belong to?
     pub file_name: OsString,
+    /// How the [`Link`] was written, if it came from a scanner.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub kind: Option<LinkKind>,
+    /// The [`Link`]'s text (or an image's alt text), if it has any.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub text: Option<String>,
+    /// The [`Link`]'s title, if it has one.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub title: Option<String>,
+    /// The label of the reference definition this [`Link`] refers to, if it
+    /// was written as a markdown reference (e.g. `[text][label]`).
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub reference: Option<String>,
+    /// Was this [`Link`] suppressed by a comment like
+    /// `<!-- linkcheck-ignore -->`, meaning it shouldn't be checked?
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub suppressed: bool,
 }
 
 impl Link {
@@ -168,6 +192,11 @@ impl Link {
             span,
             file,
             file_name,
+            kind: None,
+            text: None,
+            title: None,
+            reference: None,
+            suppressed: false,
         }
     }
 
@@ -180,6 +209,13 @@ impl Link {
 mod tests {
     use super::*;
 
+    /// Create a [`Link`] to use as a test fixture.
+    pub(crate) fn link<S: Into<String>>(href: S) -> Link {
+        let mut files = codespan::Files::new();
+        let file = files.add("README.md", "");
+        Link::new(href, Default::default(), file, "README.md".into())
+    }
+
     #[test]
     fn parse_into_categories() {
         let inputs = vec![
@@ -0,0 +1,418 @@
+//! A command-line tool for checking all the links in a directory tree.
+
+use clap::{Parser, ValueEnum};
+use codespan::Files;
+use codespan_reporting::term::{
+    self,
+    termcolor::{ColorChoice, StandardStream},
+};
+use linkcheck::{
+    config::Config,
+    fix::{self, FileFix},
+    reporting::{self, Report},
+    scanners::ScannedLink,
+    validation::{validate_in_directories, Options, Outcomes},
+    BasicContext, Link,
+};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use walkdir::{DirEntry, WalkDir};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::init();
+    let args = Args::parse();
+
+    match run(&args).await {
+        Ok(outcomes) if outcomes.invalid.is_empty() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(2)
+        },
+    }
+}
+
+/// Check the links in every markdown and HTML file in a directory.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The directory to check.
+    #[arg(default_value = ".")]
+    directory: PathBuf,
+    /// Read settings from this file (defaults to `linkcheck.toml` in the
+    /// current directory, if there is one). Flags given on the command line
+    /// take precedence.
+    #[arg(short, long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Links starting with a `/` are resolved relative to this directory
+    /// (defaults to the directory being checked).
+    #[arg(short, long)]
+    root_directory: Option<PathBuf>,
+    /// The file to use when a link points to a directory (defaults to
+    /// `index.html`).
+    #[arg(long)]
+    default_file: Option<OsString>,
+    /// Extra extensions to try when a link's file doesn't exist, written as
+    /// `EXT=ALT[,ALT...]` (e.g. `md=html`).
+    #[arg(
+        long = "alternate-extension",
+        value_name = "MAPPING",
+        value_parser = parse_alternate_extension
+    )]
+    alternate_extensions: Vec<(String, Vec<String>)>,
+    /// Allow links to point outside of the root directory.
+    #[arg(long)]
+    allow_traversal: bool,
+    /// How results should be reported.
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+    /// Locations in a SARIF report are relative to this directory (defaults
+    /// to the current directory).
+    #[arg(long, value_name = "DIR")]
+    sarif_source_root: Option<PathBuf>,
+    /// Remember the results of web requests in this file so they can be
+    /// skipped next time.
+    #[arg(long, value_name = "FILE")]
+    cache_file: Option<PathBuf>,
+    /// Rewrite links which can be fixed automatically (permanent redirects,
+    /// `http://` links which also work over `https://`, and misspelled
+    /// anchors).
+    #[arg(long)]
+    fix: bool,
+    /// Print the changes `--fix` would make as a diff instead of writing
+    /// them (to stderr if the `--format` writes its report to stdout).
+    #[arg(long, requires = "fix")]
+    dry_run: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, ValueEnum)]
+enum Format {
+    /// Print `rustc`-style errors to stderr.
+    Human,
+    /// Write a JSON report to stdout.
+    Json,
+    /// Write a JUnit XML report to stdout.
+    Junit,
+    /// Write a SARIF 2.1 report to stdout.
+    Sarif,
+}
+
+impl Format {
+    /// Does this format write a machine-readable report to stdout?
+    fn uses_stdout(self) -> bool { self != Format::Human }
+}
+
+impl Args {
+    /// The config file to load, if there is one.
+    fn config_file(&self) -> Option<PathBuf> {
+        match self.config {
+            Some(ref config) => Some(config.clone()),
+            None => Some(PathBuf::from(Config::FILE_NAME))
+                .filter(|config| config.is_file()),
+        }
+    }
+
+    fn context(&self) -> io::Result<BasicContext> {
+        let mut ctx = match self.config_file() {
+            Some(config) => {
+                Config::load_context(config).map_err(io::Error::other)?
+            },
+            None => BasicContext::default(),
+        };
+
+        if let Some(ref cache_file) = self.cache_file {
+            ctx = ctx.set_cache_file(cache_file)?;
+        }
+        ctx.options = self.options(ctx.options.clone())?;
+
+        Ok(ctx)
+    }
+
+    /// Override the [`Options`] from the config file with any flags which
+    /// were given on the command line.
+    fn options(&self, mut options: Options) -> io::Result<Options> {
+        if let Some(ref root_directory) = self.root_directory {
+            options = options.with_root_directory(root_directory)?;
+        } else if options.root_directory().is_none() {
+            options = options.with_root_directory(&self.directory)?;
+        }
+
+        if let Some(ref default_file) = self.default_file {
+            options = options.set_default_file(default_file.clone());
+        }
+
+        if self.allow_traversal {
+            options = options.set_links_may_traverse_the_root_directory(true);
+        }
+
+        if !self.alternate_extensions.is_empty() {
+            options = options
+                .set_alternate_extensions(self.alternate_extensions.clone());
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_alternate_extension(
+    mapping: &str,
+) -> Result<(String, Vec<String>), String> {
+    let (ext, alternatives) = mapping.split_once('=').ok_or_else(|| {
+        format!("expected \"EXT=ALT\", found \"{}\"", mapping)
+    })?;
+
+    Ok((
+        ext.to_string(),
+        alternatives.split(',').map(String::from).collect(),
+    ))
+}
+
+async fn run(args: &Args) -> io::Result<Outcomes> {
+    let ctx = args.context()?;
+
+    let mut files = Files::new();
+    let links = collect_links(&args.directory, &mut files)?;
+
+    // links are resolved relative to the directory containing their document,
+    // but checked together so they share the same limits and requests
+    let links = links.into_iter().flat_map(|(directory, links)| {
+        links.into_iter().map(move |link| (directory.clone(), link))
+    });
+    let outcomes = validate_in_directories(links, &ctx).await;
+
+    ctx.save_cache()?;
+    report(args, &files, &outcomes)?;
+
+    if args.fix {
+        let fixes = fix::fixes(&files, &outcomes, &ctx).await;
+        apply_fixes(args, &files, &fixes)?;
+    }
+
+    log::info!(
+        "{} valid, {} invalid, {} warnings, {} ignored, {} unknown",
+        outcomes.valid.len(),
+        outcomes.invalid.len(),
+        outcomes.warnings.len(),
+        outcomes.ignored.len(),
+        outcomes.unknown_category.len(),
+    );
+
+    Ok(outcomes)
+}
+
+fn report(
+    args: &Args,
+    files: &Files<String>,
+    outcomes: &Outcomes,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+
+    match args.format {
+        Format::Human => {
+            let mut stderr = StandardStream::stderr(ColorChoice::Auto);
+            let config = term::Config::default();
+
+            for diagnostic in reporting::diagnostics(outcomes) {
+                term::emit(&mut stderr, &config, files, &diagnostic)
+                    .map_err(io::Error::other)?;
+            }
+
+            Ok(())
+        },
+        Format::Json => {
+            reporting::write_json(stdout.lock(), &Report::new(files, outcomes))
+        },
+        Format::Junit => {
+            reporting::write_junit(stdout.lock(), &Report::new(files, outcomes))
+        },
+        Format::Sarif => {
+            let source_root = match args.sarif_source_root {
+                Some(ref source_root) => source_root.clone(),
+                None => std::env::current_dir()?,
+            };
+
+            reporting::write_sarif(
+                stdout.lock(),
+                &Report::new(files, outcomes),
+                &source_root,
+            )
+        },
+    }
+}
+
+/// Write each [`FileFix`] back to its document, or print them as a diff.
+fn apply_fixes(
+    args: &Args,
+    files: &Files<String>,
+    fixes: &[FileFix],
+) -> io::Result<()> {
+    if args.dry_run {
+        let diff = fix::diff(files, fixes).map_err(io::Error::other)?;
+        // don't mix the diff into a report someone else needs to parse
+        if args.format.uses_stdout() {
+            eprint!("{}", diff);
+        } else {
+            print!("{}", diff);
+        }
+        return Ok(());
+    }
+
+    for file_fix in fixes {
+        let path = files.name(file_fix.file);
+        log::info!(
+            "Fixing {} links in \"{}\"",
+            file_fix.edits.len(),
+            Path::new(path).display()
+        );
+        let fixed = file_fix
+            .apply(files.source(file_fix.file))
+            .map_err(io::Error::other)?;
+        std::fs::write(path, fixed)?;
+    }
+
+    Ok(())
+}
+
+/// Scan every document under a directory, returning its links grouped by the
+/// directory they should be resolved relative to.
+fn collect_links(
+    directory: &Path,
+    files: &mut Files<String>,
+) -> io::Result<HashMap<PathBuf, Vec<Link>>> {
+    let mut links: HashMap<PathBuf, Vec<Link>> = HashMap::new();
+
+    let entries = WalkDir::new(directory)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !is_hidden(entry));
+
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+
+        let scanner = match scanner_for(path) {
+            Some(scanner) if entry.file_type().is_file() => scanner,
+            _ => continue,
+        };
+
+        log::debug!("Scanning \"{}\"", path.display());
+        let src = std::fs::read_to_string(path)?;
+        let file_name = entry.file_name().to_os_string();
+        let parent = path.parent().unwrap_or(directory).to_path_buf();
+
+        let found: Vec<_> = scanner(&src).collect();
+        let file_id = files.add(path.display().to_string(), src);
+
+        links
+            .entry(parent)
+            .or_default()
+            .extend(
+                found
+                    .into_iter()
+                    .map(|link| link.into_link(file_id, file_name.clone())),
+            );
+    }
+
+    Ok(links)
+}
+
+type Scanner = fn(&str) -> Box<dyn Iterator<Item = ScannedLink> + '_>;
+
+/// Pick the scanner to use based on a file's extension.
+fn scanner_for(path: &Path) -> Option<Scanner> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    match extension.as_str() {
+        "md" | "markdown" => {
+            Some(|src| Box::new(linkcheck::scanners::markdown_links(src)))
+        },
+        "html" | "htm" => {
+            Some(|src| Box::new(linkcheck::scanners::html_links(src)))
+        },
+        _ => None,
+    }
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry
+        .file_name()
+        .to_str()
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_alternate_extension_mappings() {
+        let got = parse_alternate_extension("md=html,htm").unwrap();
+
+        assert_eq!(
+            got,
+            (
+                String::from("md"),
+                vec![String::from("html"), String::from("htm")]
+            )
+        );
+        assert!(parse_alternate_extension("md").is_err());
+    }
+
+    #[test]
+    fn flags_take_precedence_over_the_config_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = temp.path().join(Config::FILE_NAME);
+        std::fs::write(
+            &config,
+            "default-file = \"README.md\"\nallow-traversal = true\n",
+        )
+        .unwrap();
+        let config = config.display().to_string();
+        let directory = temp.path().display().to_string();
+
+        let args = Args::parse_from(["linkcheck", "-c", &config, &directory]);
+        let ctx = args.context().unwrap();
+        assert_eq!(ctx.options.default_file(), "README.md");
+        assert!(ctx.options.links_may_traverse_the_root_directory());
+
+        let args = Args::parse_from([
+            "linkcheck",
+            "-c",
+            &config,
+            "--default-file",
+            "index.md",
+            &directory,
+        ]);
+        let ctx = args.context().unwrap();
+        assert_eq!(ctx.options.default_file(), "index.md");
+        assert_eq!(
+            ctx.options.root_directory(),
+            Some(dunce::canonicalize(temp.path()).unwrap().as_path())
+        );
+    }
+
+    #[test]
+    fn links_are_grouped_by_their_document_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let nested = temp.path().join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(temp.path().join("README.md"), "[a](nested/)").unwrap();
+        std::fs::write(nested.join("index.html"), r#"<a href="..">"#).unwrap();
+        std::fs::write(nested.join("notes.txt"), "https://example.com/")
+            .unwrap();
+        let mut files = Files::new();
+
+        let got = collect_links(temp.path(), &mut files).unwrap();
+
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[temp.path()][0].href, "nested/");
+        assert_eq!(got[&nested][0].href, "..");
+        assert_eq!(got[&nested][0].file_name, "index.html");
+    }
+}
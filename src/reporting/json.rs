@@ -0,0 +1,38 @@
+use crate::reporting::Report;
+use std::io::{self, Write};
+
+/// Write a [`Report`] as pretty-printed JSON.
+///
+/// # Examples
+///
+/// ```rust
+/// use linkcheck::reporting::{LinkReport, Report, Status};
+/// # use codespan::Span;
+///
+/// let report = Report {
+///     links: vec![LinkReport {
+///         href: String::from("./README.md"),
+///         file: String::from("index.md"),
+///         kind: None,
+///         span: Span::new(0, 16),
+///         line: 1,
+///         column: 1,
+///         end_line: 1,
+///         end_column: 17,
+///         status: Status::Valid,
+///         reason: None,
+///         notes: Vec::new(),
+///         suggestions: Vec::new(),
+///     }],
+/// };
+/// let mut buffer = Vec::new();
+///
+/// linkcheck::reporting::write_json(&mut buffer, &report).unwrap();
+///
+/// let json = String::from_utf8(buffer).unwrap();
+/// assert!(json.contains(r#""status": "valid""#));
+/// ```
+pub fn write_json<W: Write>(writer: W, report: &Report) -> io::Result<()> {
+    serde_json::to_writer_pretty(writer, report)?;
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use crate::reporting::{LinkReport, Report, Status};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+/// Write a [`Report`] as [JUnit XML][junit], the format understood by most CI
+/// systems (Jenkins, GitLab, Azure Pipelines, etc.).
+///
+/// Each document becomes a `<testsuite>` and each link becomes a
+/// `<testcase>`. Broken links are reported as failures with the reason and
+/// any notes, links with a warning pass with the warning written to
+/// `<system-out>`, while ignored links and links we don't know how to check
+/// are marked as skipped.
+///
+/// [junit]: https://github.com/testmoapp/junitxml
+pub fn write_junit<W: Write>(mut writer: W, report: &Report) -> io::Result<()> {
+    let mut suites: BTreeMap<&str, Vec<TestCase<'_>>> = BTreeMap::new();
+    for link in &report.links {
+        let testcases = suites.entry(&link.file).or_default();
+
+        // a link with several warnings is still only one test
+        match testcases.last_mut() {
+            Some(testcase) if is_another_warning(testcase, link) => {
+                testcase.push(link)
+            },
+            _ => testcases.push(vec![link]),
+        }
+    }
+
+    let all: Vec<_> = suites.values().flatten().collect();
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<testsuites name="linkcheck" tests="{}" failures="{}" skipped="{}">"#,
+        all.len(),
+        count(all.iter().copied(), |s| s == Status::Invalid),
+        count(all.iter().copied(), is_skipped),
+    )?;
+
+    for (file, testcases) in &suites {
+        writeln!(
+            writer,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}">"#,
+            escape(file),
+            testcases.len(),
+            count(testcases.iter(), |s| s == Status::Invalid),
+            count(testcases.iter(), is_skipped),
+        )?;
+
+        for testcase in testcases {
+            write_testcase(&mut writer, testcase)?;
+        }
+
+        writeln!(writer, "  </testsuite>")?;
+    }
+
+    writeln!(writer, "</testsuites>")?;
+
+    Ok(())
+}
+
+/// Every [`LinkReport`] for a single link.
+type TestCase<'a> = Vec<&'a LinkReport>;
+
+/// Is this [`LinkReport`] another warning for the same link?
+fn is_another_warning(testcase: &[&LinkReport], link: &LinkReport) -> bool {
+    let first = testcase[0];
+
+    first.status == Status::Warning
+        && link.status == Status::Warning
+        && first.span == link.span
+        && first.href == link.href
+}
+
+fn write_testcase<W: Write>(
+    writer: &mut W,
+    testcase: &[&LinkReport],
+) -> io::Result<()> {
+    let link = testcase[0];
+    let name = format!("{} ({}:{})", link.href, link.line, link.column);
+    write!(
+        writer,
+        r#"    <testcase name="{}" classname="{}""#,
+        escape(&name),
+        escape(&link.file),
+    )?;
+
+    match link.status {
+        Status::Valid => writeln!(writer, " />"),
+        Status::Invalid => {
+            writeln!(writer, ">")?;
+            let reason = link.reason.as_deref().unwrap_or("Invalid link");
+            write!(
+                writer,
+                r#"      <failure message="{}" type="invalid-link">"#,
+                escape(reason)
+            )?;
+            for note in &link.notes {
+                write!(writer, "{}&#10;", escape(note))?;
+            }
+            writeln!(writer, "</failure>")?;
+            writeln!(writer, "    </testcase>")
+        },
+        Status::Warning => {
+            writeln!(writer, ">")?;
+            write!(writer, "      <system-out>")?;
+            for warning in testcase {
+                if let Some(ref reason) = warning.reason {
+                    write!(writer, "warning: {}&#10;", escape(reason))?;
+                }
+                for note in &warning.notes {
+                    write!(writer, "{}&#10;", escape(note))?;
+                }
+            }
+            writeln!(writer, "</system-out>")?;
+            writeln!(writer, "    </testcase>")
+        },
+        Status::Ignored | Status::UnknownCategory => {
+            let message = match link.reason {
+                Some(ref reason) => escape(reason),
+                None if link.status == Status::Ignored => {
+                    String::from("Ignored")
+                },
+                None => {
+                    String::from("Unable to determine how to check this link")
+                },
+            };
+            writeln!(writer, ">")?;
+            writeln!(writer, r#"      <skipped message="{}" />"#, message)?;
+            writeln!(writer, "    </testcase>")
+        },
+    }
+}
+
+fn is_skipped(status: Status) -> bool {
+    matches!(status, Status::Ignored | Status::UnknownCategory)
+}
+
+fn count<'a, 'b: 'a, I, P>(testcases: I, predicate: P) -> usize
+where
+    I: Iterator<Item = &'a TestCase<'b>>,
+    P: Fn(Status) -> bool,
+{
+    testcases
+        .filter(|testcase| predicate(testcase[0].status))
+        .count()
+}
+
+/// Escape text so it can be used inside a XML attribute or element.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            // XML 1.0 doesn't allow most control characters
+            c if c.is_control() && c != '\t' && c != '\r' => {},
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codespan::Span;
+
+    fn link(href: &str, status: Status, reason: Option<&str>) -> LinkReport {
+        LinkReport {
+            href: href.to_string(),
+            file: String::from("README.md"),
+            kind: None,
+            span: Span::new(0, 1),
+            line: 1,
+            column: 2,
+            end_line: 1,
+            end_column: 3,
+            status,
+            reason: reason.map(String::from),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn one_testcase_per_link() {
+        let report = Report {
+            links: vec![
+                link("./a.md", Status::Valid, None),
+                link(
+                    "./b&c.md",
+                    Status::Invalid,
+                    Some("The file doesn't exist"),
+                ),
+                link("mailto:x@example.com", Status::Ignored, None),
+            ],
+        };
+        let mut buffer = Vec::new();
+
+        write_junit(&mut buffer, &report).unwrap();
+
+        let got = String::from_utf8(buffer).unwrap();
+        let should_be = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="linkcheck" tests="3" failures="1" skipped="1">
+  <testsuite name="README.md" tests="3" failures="1" skipped="1">
+    <testcase name="./a.md (1:2)" classname="README.md" />
+    <testcase name="./b&amp;c.md (1:2)" classname="README.md">
+      <failure message="The file doesn&apos;t exist" type="invalid-link"></failure>
+    </testcase>
+    <testcase name="mailto:x@example.com (1:2)" classname="README.md">
+      <skipped message="Ignored" />
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn a_link_with_several_warnings_is_one_testcase() {
+        let report = Report {
+            links: vec![
+                link("http://a.com/", Status::Warning, Some("Insecure")),
+                link("http://a.com/", Status::Warning, Some("Redirected")),
+            ],
+        };
+        let mut buffer = Vec::new();
+
+        write_junit(&mut buffer, &report).unwrap();
+
+        let got = String::from_utf8(buffer).unwrap();
+        assert!(got.contains(r#"<testsuites name="linkcheck" tests="1""#));
+        assert!(got.contains(
+            "<system-out>warning: Insecure&#10;warning: Redirected&#10;"
+        ));
+    }
+}
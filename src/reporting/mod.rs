@@ -0,0 +1,319 @@
+//! Turn the [`Outcomes`] of validation into something people (or other tools)
+//! can read.
+//!
+//! For humans, [`diagnostics()`] creates `rustc`-style error and warning
+//! messages. For machines, the [`Outcomes`] can be flattened into a
+//! [`Report`] and written as JUnit XML ([`write_junit()`]), or with the
+//! `serde-1` feature, as JSON ([`write_json()`]) or SARIF
+//! ([`write_sarif()`]).
+//!
+//! # Examples
+//!
+//! Once you've got some [`Outcomes`], you can use [`codespan_reporting`] to
+//! print `rustc`-style error messages.
+//!
+//! ```rust,no_run
+//! use codespan::Files;
+//! use codespan_reporting::term::{
+//!     self,
+//!     termcolor::{ColorChoice, StandardStream},
+//! };
+//! use linkcheck::{validation::Outcomes, reporting};
+//!
+//! # fn run(files: Files<String>, outcomes: Outcomes) {
+//! let mut stderr = StandardStream::stderr(ColorChoice::Auto);
+//! let config = term::Config::default();
+//!
+//! for diagnostic in reporting::diagnostics(&outcomes) {
+//!     term::emit(&mut stderr, &config, &files, &diagnostic).unwrap();
+//! }
+//! # }
+//! ```
+
+#[cfg(feature = "serde-1")]
+mod json;
+mod junit;
+mod report;
+#[cfg(feature = "serde-1")]
+mod sarif;
+
+#[cfg(feature = "serde-1")]
+pub use json::write_json;
+pub use junit::write_junit;
+pub use report::{LinkReport, Report, Status};
+#[cfg(feature = "serde-1")]
+pub use sarif::write_sarif;
+
+use crate::validation::{InvalidLink, LinkWarning, Outcomes, Reason, Warning};
+use codespan::FileId;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use std::error::Error;
+
+/// Create a [`Diagnostic`] for every broken link, followed by a warning for
+/// every link with a [`Warning`] attached.
+pub fn diagnostics(
+    outcomes: &Outcomes,
+) -> impl Iterator<Item = Diagnostic<FileId>> + '_ {
+    outcomes
+        .invalid
+        .iter()
+        .map(diagnostic)
+        .chain(outcomes.warnings.iter().map(warning_diagnostic))
+}
+
+/// Create a [`Diagnostic`] explaining why a link is broken.
+///
+/// The primary label will point at the link in its original document, and
+/// any extra information we have (e.g. the paths which were checked, or the
+/// underlying IO error) will be attached as notes.
+pub fn diagnostic(invalid: &InvalidLink) -> Diagnostic<FileId> {
+    let InvalidLink {
+        link,
+        reason,
+        suggestions,
+    } = invalid;
+    let mut notes = notes(reason);
+    notes.extend(suggestion_note(suggestions));
+
+    Diagnostic::error()
+        .with_message(message(&link.href, reason))
+        .with_labels(vec![Label::primary(link.file, link.span)
+            .with_message(reason.to_string())])
+        .with_notes(notes)
+}
+
+/// Create a warning-level [`Diagnostic`] for a link which works, but should
+/// probably be looked at.
+pub fn warning_diagnostic(warning: &LinkWarning) -> Diagnostic<FileId> {
+    let LinkWarning { link, warning, .. } = warning;
+
+    Diagnostic::warning()
+        .with_message(warning_message(&link.href, warning))
+        .with_labels(vec![Label::primary(link.file, link.span)
+            .with_message(warning.to_string())])
+        .with_notes(warning_notes(warning))
+}
+
+fn message(href: &str, reason: &Reason) -> String {
+    match reason {
+        Reason::TraversesParentDirectories => {
+            format!("\"{}\" links outside of the root directory", href)
+        },
+        Reason::NotFound { .. } => format!("File not found: \"{}\"", href),
+        Reason::Io(_) if reason.file_not_found() => {
+            format!("File not found: \"{}\"", href)
+        },
+        Reason::Io(_) => format!("Unable to check \"{}\"", href),
+        Reason::File | Reason::Dom => {
+            format!("\"{}\" links to an anchor which doesn't exist", href)
+        },
+        Reason::Web(_) if reason.timed_out() => {
+            format!("Timed out while checking \"{}\"", href)
+        },
+        Reason::Web(_) => format!("Unable to fetch \"{}\"", href),
+        Reason::Cached { .. } => {
+            format!("\"{}\" was broken when it was last checked", href)
+        },
+        Reason::Warning(warning) => warning_message(href, warning),
+    }
+}
+
+fn warning_message(href: &str, warning: &Warning) -> String {
+    match warning {
+        Warning::PermanentRedirect { final_url, .. } => format!(
+            "\"{}\" has moved, consider linking to \"{}\" instead",
+            href, final_url
+        ),
+        Warning::InsecureLink => {
+            format!("\"{}\" should use https:// instead of http://", href)
+        },
+        Warning::SlowResponse { .. } => {
+            format!("\"{}\" was slow to respond", href)
+        },
+        Warning::CaseInsensitiveFragment { .. } => {
+            format!("\"{}\" only matches an anchor when case is ignored", href)
+        },
+        Warning::DeprecatedAnchor { .. } => {
+            format!("\"{}\" links to a deprecated anchor", href)
+        },
+    }
+}
+
+pub(crate) fn warning_notes(warning: &Warning) -> Vec<String> {
+    warning
+        .redirects()
+        .iter()
+        .map(|redirect| {
+            format!(
+                "\"{}\" redirects to \"{}\" ({})",
+                redirect.from, redirect.to, redirect.status
+            )
+        })
+        .collect()
+}
+
+/// Turn the [`InvalidLink::suggestions`] into something like `did you mean
+/// "#getting-started"?`.
+pub(crate) fn suggestion_note(suggestions: &[String]) -> Option<String> {
+    if suggestions.is_empty() {
+        return None;
+    }
+
+    let quoted: Vec<_> = suggestions
+        .iter()
+        .map(|suggestion| format!("\"{}\"", suggestion))
+        .collect();
+
+    Some(format!("did you mean {}?", quoted.join(" or ")))
+}
+
+pub(crate) fn notes(reason: &Reason) -> Vec<String> {
+    let mut notes = Vec::new();
+
+    if let Reason::NotFound { tried } = reason {
+        notes.extend(
+            tried
+                .iter()
+                .map(|path| format!("Tried \"{}\"", path.display())),
+        );
+    }
+
+    if let Reason::Warning(warning) = reason {
+        notes.extend(warning_notes(warning));
+    }
+
+    if let Reason::Cached { .. } = reason {
+        notes.push(String::from(
+            "This result came from the cache, so the link wasn't re-checked",
+        ));
+    }
+
+    if let Reason::Web(error) = reason {
+        if error.attempts() > 1 {
+            notes.push(format!("Gave up after {} attempts", error.attempts()));
+        }
+    }
+
+    let mut source = reason.source();
+    while let Some(error) = source {
+        notes.push(error.to_string());
+        source = error.source();
+    }
+
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{validation::Redirect, Link};
+    use codespan::{Files, Span};
+    use codespan_reporting::diagnostic::Severity;
+    use std::{io, path::PathBuf};
+
+    fn invalid_link(reason: Reason) -> (Files<&'static str>, InvalidLink) {
+        let mut files = Files::new();
+        let src = "Some [broken link](./missing.md).";
+        let file = files.add("README.md", src);
+        let link = Link::new(
+            "./missing.md",
+            Span::new(5, 32),
+            file,
+            "README.md".into(),
+        );
+
+        (
+            files,
+            InvalidLink {
+                link,
+                reason,
+                suggestions: Vec::new(),
+            },
+        )
+    }
+
+    #[test]
+    fn missing_files_mention_each_path_that_was_tried() {
+        let tried = vec![
+            PathBuf::from("/docs/missing.md"),
+            PathBuf::from("/docs/missing.html"),
+        ];
+        let (_files, invalid) = invalid_link(Reason::NotFound { tried });
+
+        let got = diagnostic(&invalid);
+
+        assert_eq!(got.severity, Severity::Error);
+        assert_eq!(got.message, "File not found: \"./missing.md\"");
+        assert_eq!(got.labels.len(), 1);
+        assert_eq!(got.labels[0].file_id, invalid.link.file);
+        assert_eq!(got.labels[0].range, 5..32);
+        assert_eq!(got.labels[0].message, "The file doesn't exist");
+        assert_eq!(
+            got.notes,
+            vec!["Tried \"/docs/missing.md\"", "Tried \"/docs/missing.html\""]
+        );
+    }
+
+    #[test]
+    fn io_errors_are_attached_as_notes() {
+        let error = io::Error::new(io::ErrorKind::PermissionDenied, "Nope");
+        let (_files, invalid) = invalid_link(Reason::Io(error));
+
+        let got = diagnostic(&invalid);
+
+        assert_eq!(got.message, "Unable to check \"./missing.md\"");
+        assert_eq!(got.notes, vec!["Nope"]);
+    }
+
+    #[test]
+    fn render_a_diagnostic() {
+        let (files, invalid) = invalid_link(Reason::File);
+        let mut writer =
+            codespan_reporting::term::termcolor::NoColor::new(Vec::<u8>::new());
+        let config = codespan_reporting::term::Config::default();
+
+        codespan_reporting::term::emit(
+            &mut writer,
+            &config,
+            &files,
+            &diagnostic(&invalid),
+        )
+        .unwrap();
+
+        let rendered = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(rendered.contains(
+            "error: \"./missing.md\" links to an anchor which doesn't exist"
+        ));
+        assert!(rendered.contains("README.md:1:6"), "{}", rendered);
+    }
+
+    #[test]
+    fn permanent_redirects_are_warnings() {
+        let (_files, InvalidLink { link, .. }) = invalid_link(Reason::Dom);
+        let redirect = Redirect {
+            from: "http://example.com/old".parse().unwrap(),
+            to: "https://example.com/new".parse().unwrap(),
+            status: 301,
+        };
+        let warning = LinkWarning {
+            link,
+            warning: Warning::PermanentRedirect {
+                final_url: redirect.to.clone(),
+                redirects: vec![redirect],
+            },
+            web_check: None,
+        };
+
+        let got = warning_diagnostic(&warning);
+
+        assert_eq!(got.severity, Severity::Warning);
+        assert_eq!(
+            got.message,
+            "\"./missing.md\" has moved, consider linking to \"https://example.com/new\" instead"
+        );
+        assert_eq!(
+            got.notes,
+            vec!["\"http://example.com/old\" redirects to \"https://example.com/new\" (301)"]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    reporting::{notes, suggestion_note, warning_notes},
+    validation::{IgnoredLink, InvalidLink, LinkWarning, Outcomes},
+    Link, LinkKind,
+};
+use codespan::{ByteIndex, Files, Span};
+
+/// A flattened, self-contained version of [`Outcomes`] which is suitable for
+/// serializing to a machine-readable format.
+///
+/// Unlike a [`Link`], every [`LinkReport`] contains the name of its document
+/// and the line/column numbers of its [`Span`], so you don't need access to
+/// the original [`Files`] to make sense of it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Report {
+    /// Every link that was checked.
+    pub links: Vec<LinkReport>,
+}
+
+impl Report {
+    /// Create a [`Report`] from some [`Outcomes`], using the [`Files`]
+    /// database to look up file names and line numbers.
+    pub fn new<S: AsRef<str>>(files: &Files<S>, outcomes: &Outcomes) -> Self {
+        let Outcomes {
+            valid,
+            invalid,
+            warnings,
+            ignored,
+            unknown_category,
+        } = outcomes;
+
+        let mut links = Vec::new();
+        links.extend(
+            valid.iter().map(|valid| {
+                LinkReport::new(files, &valid.link, Status::Valid)
+            }),
+        );
+        links.extend(
+            invalid
+                .iter()
+                .map(|invalid| LinkReport::from_invalid_link(files, invalid)),
+        );
+        links.extend(
+            warnings
+                .iter()
+                .map(|warning| LinkReport::from_link_warning(files, warning)),
+        );
+        links.extend(
+            ignored
+                .iter()
+                .map(|ignored| LinkReport::from_ignored_link(files, ignored)),
+        );
+        links.extend(
+            unknown_category.iter().map(|link| {
+                LinkReport::new(files, link, Status::UnknownCategory)
+            }),
+        );
+
+        links.sort_by(|left, right| {
+            (&left.file, left.span.start())
+                .cmp(&(&right.file, right.span.start()))
+        });
+
+        Report { links }
+    }
+
+    /// Iterate over all links with a particular [`Status`].
+    pub fn with_status(
+        &self,
+        status: Status,
+    ) -> impl Iterator<Item = &LinkReport> + '_ {
+        self.links.iter().filter(move |link| link.status == status)
+    }
+
+    /// How many links have this [`Status`]?
+    pub fn count(&self, status: Status) -> usize {
+        self.with_status(status).count()
+    }
+}
+
+/// Information about a single [`Link`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkReport {
+    /// The link itself.
+    pub href: String,
+    /// The name of the document containing this link.
+    pub file: String,
+    /// How the link was written (e.g. as an image), if known.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub kind: Option<LinkKind>,
+    /// Where the link lies in its document.
+    pub span: Span,
+    /// The (1-based) line the link starts on.
+    pub line: usize,
+    /// The (1-based) column the link starts at, measured in characters.
+    pub column: usize,
+    /// The (1-based) line the link ends on.
+    pub end_line: usize,
+    /// The (1-based) column just after the end of the link, measured in
+    /// characters.
+    pub end_column: usize,
+    /// The result of validation.
+    pub status: Status,
+    /// Why the link is invalid (or has a warning, or was ignored), if it is.
+    pub reason: Option<String>,
+    /// Any extra information about why the link is invalid (or has a
+    /// warning).
+    pub notes: Vec<String>,
+    /// Similar `href`s which would have worked, best first (see
+    /// [`InvalidLink::suggestions`]).
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub suggestions: Vec<String>,
+}
+
+impl LinkReport {
+    fn new<S: AsRef<str>>(
+        files: &Files<S>,
+        link: &Link,
+        status: Status,
+    ) -> Self {
+        let (line, column) = line_and_column(files, link, link.span.start());
+        let (end_line, end_column) =
+            line_and_column(files, link, link.span.end());
+
+        LinkReport {
+            href: link.href.clone(),
+            file: files.name(link.file).to_string_lossy().into_owned(),
+            kind: link.kind,
+            span: link.span,
+            line,
+            column,
+            end_line,
+            end_column,
+            status,
+            reason: None,
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    fn from_invalid_link<S: AsRef<str>>(
+        files: &Files<S>,
+        invalid: &InvalidLink,
+    ) -> Self {
+        let mut notes = notes(&invalid.reason);
+        notes.extend(suggestion_note(&invalid.suggestions));
+
+        LinkReport {
+            reason: Some(invalid.reason.to_string()),
+            notes,
+            suggestions: invalid.suggestions.clone(),
+            ..LinkReport::new(files, &invalid.link, Status::Invalid)
+        }
+    }
+
+    fn from_ignored_link<S: AsRef<str>>(
+        files: &Files<S>,
+        ignored: &IgnoredLink,
+    ) -> Self {
+        LinkReport {
+            reason: Some(ignored.reason.clone()),
+            ..LinkReport::new(files, &ignored.link, Status::Ignored)
+        }
+    }
+
+    fn from_link_warning<S: AsRef<str>>(
+        files: &Files<S>,
+        warning: &LinkWarning,
+    ) -> Self {
+        LinkReport {
+            reason: Some(warning.warning.to_string()),
+            notes: warning_notes(&warning.warning),
+            ..LinkReport::new(files, &warning.link, Status::Warning)
+        }
+    }
+}
+
+fn line_and_column<S: AsRef<str>>(
+    files: &Files<S>,
+    link: &Link,
+    index: ByteIndex,
+) -> (usize, usize) {
+    match files.location(link.file, index) {
+        Ok(location) => (
+            location.line.to_usize() + 1,
+            location.column.to_usize() + 1,
+        ),
+        Err(_) => (1, 1),
+    }
+}
+
+/// The result of checking a [`Link`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Status {
+    /// The link is valid.
+    Valid,
+    /// The link is broken.
+    Invalid,
+    /// The link works, but has a [`crate::validation::Warning`] attached.
+    Warning,
+    /// The link was explicitly ignored.
+    Ignored,
+    /// We don't know how to validate this sort of link.
+    UnknownCategory,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::{Reason, ValidLink};
+
+    #[test]
+    fn flatten_outcomes() {
+        let mut files = Files::new();
+        let src = "[a](./a.md)\n\n[b](./b.md) [c](mailto:c@example.com)";
+        let file = files.add("README.md", src);
+        let a = Link::new("./a.md", Span::new(0, 11), file, "README.md".into());
+        let b =
+            Link::new("./b.md", Span::new(13, 24), file, "README.md".into());
+        let c = Link::new(
+            "mailto:c@example.com",
+            Span::new(25, 51),
+            file,
+            "README.md".into(),
+        );
+        let outcomes = Outcomes {
+            valid: vec![ValidLink {
+                link: a,
+                web_check: None,
+            }],
+            invalid: vec![InvalidLink {
+                link: b,
+                reason: Reason::File,
+                suggestions: vec![String::from("./a.md#b")],
+            }],
+            warnings: Vec::new(),
+            ignored: vec![IgnoredLink {
+                link: c,
+                reason: String::from("mailto: links aren't checked"),
+            }],
+            unknown_category: Vec::new(),
+        };
+
+        let got = Report::new(&files, &outcomes);
+
+        assert_eq!(got.links.len(), 3);
+        assert_eq!(got.count(Status::Valid), 1);
+        assert_eq!(got.count(Status::Ignored), 1);
+        let invalid: Vec<_> = got.with_status(Status::Invalid).collect();
+        assert_eq!(
+            *invalid[0],
+            LinkReport {
+                href: String::from("./b.md"),
+                file: String::from("README.md"),
+                kind: None,
+                span: Span::new(13, 24),
+                line: 3,
+                column: 1,
+                end_line: 3,
+                end_column: 12,
+                status: Status::Invalid,
+                reason: Some(String::from(
+                    "The file exists, but not the anchor"
+                )),
+                notes: vec![String::from("did you mean \"./a.md#b\"?")],
+                suggestions: vec![String::from("./a.md#b")],
+            }
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::reporting::{LinkReport, Report, Status};
+use serde_json::{json, Value};
+use std::{
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+use url::Url;
+
+/// The ID used for broken links in our SARIF output.
+const RULE_ID: &str = "broken-link";
+/// The ID used for links which work, but have a warning attached.
+const WARNING_RULE_ID: &str = "link-warning";
+
+/// Write a [`Report`] in the [SARIF 2.1.0][sarif] format, which is used by
+/// GitHub code scanning and other static analysis tools.
+///
+/// Only broken links (as errors) and links with warnings (as warnings) are
+/// reported, with each result's region pointing at the link in its original
+/// document.
+///
+/// Tools like GitHub code scanning expect documents to be relative to the
+/// root of the repository, so every location is made relative to the
+/// `source_root` (normally the repository's root) and uses the `%SRCROOT%`
+/// base URI. Relative file names in the [`Report`] are assumed to be
+/// relative to the current directory, so the same document always gets the
+/// same URI no matter how it was found.
+///
+/// [sarif]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+pub fn write_sarif<W: Write>(
+    writer: W,
+    report: &Report,
+    source_root: &Path,
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(writer, &sarif(report, source_root))?;
+    Ok(())
+}
+
+fn sarif(report: &Report, source_root: &Path) -> Value {
+    let source_root = absolute(source_root);
+    let results: Vec<_> = report
+        .links
+        .iter()
+        .filter_map(|link| result(link, &source_root))
+        .collect();
+
+    let mut sarif = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": [{
+                        "id": RULE_ID,
+                        "shortDescription": { "text": "Broken link" },
+                    }, {
+                        "id": WARNING_RULE_ID,
+                        "shortDescription": { "text": "Link warning" },
+                    }],
+                },
+            },
+            // codespan measures columns in characters, not UTF-16 code units
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+
+    if let Ok(url) = Url::from_directory_path(&source_root) {
+        sarif["runs"][0]["originalUriBaseIds"] =
+            json!({ "%SRCROOT%": { "uri": url.as_str() } });
+    }
+
+    sarif
+}
+
+fn result(link: &LinkReport, source_root: &Path) -> Option<Value> {
+    let (rule, level, mut message) = match link.status {
+        Status::Invalid => {
+            let reason = link.reason.as_deref().unwrap_or("Invalid link");
+            let message = format!("\"{}\" is broken: {}", link.href, reason);
+            (RULE_ID, "error", message)
+        },
+        Status::Warning => {
+            let reason = link.reason.as_deref().unwrap_or("Warning");
+            let message = format!("\"{}\": {}", link.href, reason);
+            (WARNING_RULE_ID, "warning", message)
+        },
+        _ => return None,
+    };
+
+    for note in &link.notes {
+        message.push('\n');
+        message.push_str(note);
+    }
+
+    Some(json!({
+        "ruleId": rule,
+        "level": level,
+        "message": { "text": message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": artifact_location(&link.file, source_root),
+                "region": {
+                    "startLine": link.line,
+                    "startColumn": link.column,
+                    "endLine": link.end_line,
+                    "endColumn": link.end_column,
+                },
+            },
+        }],
+    }))
+}
+
+/// SARIF wants a URI relative to the `%SRCROOT%` (the already absolute
+/// `source_root`), with forward slashes.
+fn artifact_location(file: &str, source_root: &Path) -> Value {
+    let path = absolute(Path::new(file));
+
+    match path.strip_prefix(source_root) {
+        Ok(relative) => {
+            let segments: Vec<_> = relative
+                .components()
+                .filter(|c| *c != Component::CurDir)
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+
+            json!({ "uri": segments.join("/"), "uriBaseId": "%SRCROOT%" })
+        },
+        // a document outside the root can only be referred to by its full path
+        Err(_) => match Url::from_file_path(&path) {
+            Ok(url) => json!({ "uri": url.as_str() }),
+            Err(_) => json!({ "uri": file.replace('\\', "/") }),
+        },
+    }
+}
+
+/// Resolve a path against the current directory, canonicalizing it when it
+/// exists so symlinks and `..` don't change the result.
+fn absolute(path: &Path) -> PathBuf {
+    let current_dir = std::env::current_dir()
+        .and_then(dunce::canonicalize)
+        .unwrap_or_default();
+    let joined = current_dir.join(path);
+
+    dunce::canonicalize(&joined).unwrap_or(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codespan::Span;
+
+    #[test]
+    fn broken_links_become_results() {
+        let report = Report {
+            links: vec![
+                LinkReport {
+                    href: String::from("./missing.md"),
+                    file: String::from("./docs/README.md"),
+                    kind: None,
+                    span: Span::new(10, 20),
+                    line: 2,
+                    column: 3,
+                    end_line: 2,
+                    end_column: 13,
+                    status: Status::Invalid,
+                    reason: Some(String::from("The file doesn't exist")),
+                    notes: vec![String::from("Tried \"docs/missing.md\"")],
+                    suggestions: Vec::new(),
+                },
+                LinkReport {
+                    href: String::from("./README.md"),
+                    file: String::from("./docs/README.md"),
+                    kind: None,
+                    span: Span::new(30, 40),
+                    line: 4,
+                    column: 1,
+                    end_line: 4,
+                    end_column: 11,
+                    status: Status::Valid,
+                    reason: None,
+                    notes: Vec::new(),
+                    suggestions: Vec::new(),
+                },
+            ],
+        };
+
+        let got = sarif(&report, Path::new("."));
+
+        let results = &got["runs"][0]["results"];
+        assert_eq!(results.as_array().unwrap().len(), 1);
+        assert_eq!(
+            results[0],
+            json!({
+                "ruleId": "broken-link",
+                "level": "error",
+                "message": {
+                    "text": "\"./missing.md\" is broken: The file doesn't exist\nTried \"docs/missing.md\"",
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {
+                            "uri": "docs/README.md",
+                            "uriBaseId": "%SRCROOT%",
+                        },
+                        "region": {
+                            "startLine": 2,
+                            "startColumn": 3,
+                            "endLine": 2,
+                            "endColumn": 13,
+                        },
+                    },
+                }],
+            })
+        );
+        assert_eq!(got["version"], "2.1.0");
+    }
+
+    #[test]
+    fn uris_are_the_same_however_the_document_was_found() {
+        let current_dir = absolute(Path::new("."));
+        let inside = current_dir.join("src").join("lib.rs");
+        let inputs = vec![
+            inside.display().to_string(),
+            String::from("src/lib.rs"),
+            String::from("./src/../src/lib.rs"),
+        ];
+
+        for file in inputs {
+            assert_eq!(
+                artifact_location(&file, &current_dir),
+                json!({ "uri": "src/lib.rs", "uriBaseId": "%SRCROOT%" }),
+                "{}",
+                file
+            );
+            assert_eq!(
+                artifact_location(&file, &current_dir.join("src")),
+                json!({ "uri": "lib.rs", "uriBaseId": "%SRCROOT%" }),
+                "{}",
+                file
+            );
+        }
+    }
+}
@@ -0,0 +1,615 @@
+use crate::scanners::{suppress::Suppressions, LinkKind, ScannedLink};
+use codespan::Span;
+use std::{borrow::Cow, ops::Range};
+
+/// A scanner which extracts all links from a HTML document.
+///
+/// This looks at the `href`, `src`, `srcset`, `poster` and `action`
+/// attributes on every element (which includes things like
+/// `<link rel="stylesheet" href="...">`), as well as the URL in a
+/// `<meta http-equiv="refresh">` tag. Each [`Span`] points at the URL itself
+/// (i.e. not including quotes) so diagnostics can highlight exactly what is
+/// broken.
+///
+/// # Examples
+///
+/// ```rust
+/// # use codespan::Span;
+/// let src = r#"<a href="https://example.com/">Example</a> <img src="img.png">"#;
+///
+/// let got: Vec<_> = linkcheck::scanners::html(src).collect();
+///
+/// assert_eq!(got.len(), 2);
+/// let (href, span) = &got[0];
+/// assert_eq!(href, "https://example.com/");
+/// assert_eq!(*span, Span::new(9, 29));
+/// assert_eq!(&src[9..29], "https://example.com/");
+/// ```
+///
+/// [Suppressed links](crate::scanners) are skipped.
+pub fn html(src: &str) -> impl Iterator<Item = (String, Span)> + '_ {
+    html_links(src)
+        .filter(|link| !link.suppressed)
+        .map(|link| (link.href, link.span))
+}
+
+/// A scanner which extracts all links from a HTML document, including the
+/// ones which have been suppressed with a comment (see
+/// [`ScannedLink::suppressed`]).
+pub fn html_links(src: &str) -> impl Iterator<Item = ScannedLink> + '_ {
+    let mut tags = Tags::new(src);
+    let mut links: Vec<_> = tags
+        .by_ref()
+        .flat_map(|tag| {
+            let title = tag.attribute("title").map(|attr| {
+                decode_character_references(attr.value).into_owned()
+            });
+
+            links_in_tag(&tag).into_iter().map(move |(href, span)| {
+                let mut link = ScannedLink::new(href, span, LinkKind::Html);
+                link.title = title.clone();
+                link
+            })
+        })
+        .collect();
+
+    let suppressions = Suppressions::new(src, tags.comments);
+    for link in &mut links {
+        link.suppressed = suppressions.contains(link.span);
+    }
+
+    links.into_iter()
+}
+
+fn links_in_tag(tag: &Tag<'_>) -> Vec<(String, Span)> {
+    let mut links = Vec::new();
+
+    for attribute in &tag.attributes {
+        match attribute.name.as_str() {
+            "href" | "src" | "poster" | "action" => {
+                links.extend(link_from_value(attribute.value, attribute.start))
+            },
+            "srcset" => links.extend(
+                srcset_candidates(attribute.value).into_iter().filter_map(
+                    |(offset, url)| {
+                        link_from_value(url, attribute.start + offset)
+                    },
+                ),
+            ),
+            "content" if tag.is_meta_refresh() => {
+                if let Some((offset, url)) = meta_refresh_url(attribute.value) {
+                    links
+                        .extend(link_from_value(url, attribute.start + offset));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    links
+}
+
+/// Turn the raw text of an attribute into a link, trimming whitespace and
+/// decoding any character references.
+fn link_from_value(raw: &str, start: usize) -> Option<(String, Span)> {
+    let trimmed = raw.trim_start();
+    let start = start + (raw.len() - trimmed.len());
+    let trimmed = trimmed.trim_end();
+
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let span = Span::new(start as u32, (start + trimmed.len()) as u32);
+    Some((decode_character_references(trimmed).into_owned(), span))
+}
+
+/// Split a `srcset` attribute into its image candidate URLs, returning each
+/// URL's offset from the start of the attribute.
+///
+/// See [the spec][spec] for the gory details.
+///
+/// [spec]: https://html.spec.whatwg.org/multipage/images.html#parsing-a-srcset-attribute
+fn srcset_candidates(srcset: &str) -> Vec<(usize, &str)> {
+    let is_separator = |c: char| c.is_ascii_whitespace() || c == ',';
+    let mut candidates = Vec::new();
+    let mut cursor = 0;
+
+    while cursor < srcset.len() {
+        let rest = &srcset[cursor..];
+        let url_start = cursor
+            + rest.find(|c: char| !is_separator(c)).unwrap_or(rest.len());
+        let rest = &srcset[url_start..];
+        let url_end = url_start
+            + rest
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(rest.len());
+        let url = srcset[url_start..url_end].trim_end_matches(',');
+
+        if !url.is_empty() {
+            candidates.push((url_start, url));
+        }
+
+        if srcset[url_start..url_end].ends_with(',') {
+            // no descriptors, the comma terminated the candidate
+            cursor = url_end;
+        } else {
+            // skip past the descriptors (e.g. "2x" or "480w")
+            let rest = &srcset[url_end..];
+            cursor =
+                url_end + rest.find(',').map(|i| i + 1).unwrap_or(rest.len());
+        }
+    }
+
+    candidates
+}
+
+/// Extract the URL from the `content` attribute of a
+/// `<meta http-equiv="refresh">` tag (e.g. `"5; url=https://example.com/"`).
+fn meta_refresh_url(content: &str) -> Option<(usize, &str)> {
+    let bytes = content.as_bytes();
+    let mut cursor = 0;
+
+    let skip_whitespace = |mut cursor: usize| {
+        while cursor < bytes.len() && bytes[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+        cursor
+    };
+
+    cursor = skip_whitespace(cursor);
+    while cursor < bytes.len()
+        && (bytes[cursor].is_ascii_digit() || bytes[cursor] == b'.')
+    {
+        cursor += 1;
+    }
+    cursor = skip_whitespace(cursor);
+
+    if cursor < bytes.len() && (bytes[cursor] == b';' || bytes[cursor] == b',')
+    {
+        cursor += 1;
+    }
+    cursor = skip_whitespace(cursor);
+
+    if bytes
+        .get(cursor..cursor + 3)
+        .is_some_and(|b| b.eq_ignore_ascii_case(b"url"))
+    {
+        let after_url = skip_whitespace(cursor + 3);
+        if bytes.get(after_url) == Some(&b'=') {
+            cursor = skip_whitespace(after_url + 1);
+        }
+    }
+
+    let (start, end) = match bytes.get(cursor) {
+        Some(&quote) if quote == b'"' || quote == b'\'' => {
+            let start = cursor + 1;
+            let end = content[start..]
+                .find(quote as char)
+                .map(|i| start + i)
+                .unwrap_or(content.len());
+            (start, end)
+        },
+        _ => (cursor, content.len()),
+    };
+
+    let url = &content[start..end];
+    if url.trim().is_empty() {
+        None
+    } else {
+        Some((start, url))
+    }
+}
+
+/// Decode the character references most commonly found in URLs.
+fn decode_character_references(src: &str) -> Cow<'_, str> {
+    if !src.contains('&') {
+        return Cow::Borrowed(src);
+    }
+
+    let mut decoded = String::with_capacity(src.len());
+    let mut rest = src;
+
+    while let Some(ampersand) = rest.find('&') {
+        decoded.push_str(&rest[..ampersand]);
+        rest = &rest[ampersand..];
+
+        let reference = rest
+            .find(';')
+            .map(|semicolon| &rest[1..semicolon])
+            .and_then(|name| Some((name, decode_reference(name)?)));
+
+        match reference {
+            Some((name, c)) => {
+                decoded.push(c);
+                rest = &rest[name.len() + 2..];
+            },
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+fn decode_reference(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            std::char::from_u32(code)
+        },
+    }
+}
+
+/// A start tag and its attributes.
+#[derive(Debug, Clone, PartialEq)]
+struct Tag<'a> {
+    /// The element's name, normalised to lowercase.
+    name: String,
+    attributes: Vec<Attribute<'a>>,
+}
+
+impl<'a> Tag<'a> {
+    fn attribute(&self, name: &str) -> Option<&Attribute<'a>> {
+        self.attributes.iter().find(|attr| attr.name == name)
+    }
+
+    fn is_meta_refresh(&self) -> bool {
+        self.name == "meta"
+            && self
+                .attribute("http-equiv")
+                .map(|attr| attr.value.trim().eq_ignore_ascii_case("refresh"))
+                .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Attribute<'a> {
+    /// The attribute's name, normalised to lowercase.
+    name: String,
+    /// The raw (undecoded) value.
+    value: &'a str,
+    /// Where [`Attribute::value`] starts in the original source text.
+    start: usize,
+}
+
+/// A minimal HTML tokenizer which only cares about start tags.
+///
+/// We can't use `kuchiki` (or `html5ever`) here because the DOM they produce
+/// doesn't keep track of where each node came from, and we need byte offsets
+/// to generate accurate [`Span`]s.
+#[derive(Debug)]
+struct Tags<'a> {
+    src: &'a str,
+    cursor: usize,
+    /// Every comment we've skipped over so far.
+    comments: Vec<Range<usize>>,
+}
+
+impl<'a> Tags<'a> {
+    /// Elements whose contents are raw text and should never be scanned for
+    /// tags.
+    const RAW_TEXT_ELEMENTS: &'static [&'static str] =
+        &["script", "style", "textarea", "title", "xmp"];
+
+    fn new(src: &'a str) -> Self {
+        Tags {
+            src,
+            cursor: 0,
+            comments: Vec::new(),
+        }
+    }
+
+    fn rest(&self) -> &'a str { &self.src[self.cursor..] }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.cursor).copied()
+    }
+
+    fn skip_past(&mut self, needle: &str) {
+        self.cursor = match self.rest().find(needle) {
+            Some(index) => self.cursor + index + needle.len(),
+            None => self.src.len(),
+        };
+    }
+
+    fn skip_while<P: Fn(u8) -> bool>(&mut self, predicate: P) -> &'a str {
+        let start = self.cursor;
+        while self.peek().map(&predicate).unwrap_or(false) {
+            self.cursor += 1;
+        }
+        &self.src[start..self.cursor]
+    }
+
+    fn skip_raw_text(&mut self, element: &str) {
+        let closing_tag = format!("</{}", element);
+
+        while let Some(index) = self.rest().find("</") {
+            let start = self.cursor + index;
+            let end = start + closing_tag.len();
+
+            if self
+                .src
+                .get(start..end)
+                .map(|tag| tag.eq_ignore_ascii_case(&closing_tag))
+                .unwrap_or(false)
+            {
+                self.cursor = start;
+                return;
+            }
+
+            self.cursor = start + 2;
+        }
+
+        self.cursor = self.src.len();
+    }
+
+    fn start_tag(&mut self) -> Tag<'a> {
+        let name = self
+            .skip_while(|b| !b.is_ascii_whitespace() && b != b'/' && b != b'>')
+            .to_ascii_lowercase();
+        let mut attributes = Vec::new();
+
+        loop {
+            self.skip_while(|b| b.is_ascii_whitespace() || b == b'/');
+
+            match self.peek() {
+                None => break,
+                Some(b'>') => {
+                    self.cursor += 1;
+                    break;
+                },
+                Some(_) => {},
+            }
+
+            let attribute_name = self
+                .skip_while(|b| {
+                    !b.is_ascii_whitespace() && !matches!(b, b'=' | b'>' | b'/')
+                })
+                .to_ascii_lowercase();
+            self.skip_while(|b| b.is_ascii_whitespace());
+
+            let (value, start) = if self.peek() == Some(b'=') {
+                self.cursor += 1;
+                self.skip_while(|b| b.is_ascii_whitespace());
+                self.attribute_value()
+            } else {
+                ("", self.cursor)
+            };
+
+            attributes.push(Attribute {
+                name: attribute_name,
+                value,
+                start,
+            });
+        }
+
+        Tag { name, attributes }
+    }
+
+    fn attribute_value(&mut self) -> (&'a str, usize) {
+        match self.peek() {
+            Some(quote) if quote == b'"' || quote == b'\'' => {
+                self.cursor += 1;
+                let start = self.cursor;
+                let value = self.skip_while(|b| b != quote);
+                // skip the closing quote
+                self.cursor = (self.cursor + 1).min(self.src.len());
+                (value, start)
+            },
+            _ => {
+                let start = self.cursor;
+                let value =
+                    self.skip_while(|b| !b.is_ascii_whitespace() && b != b'>');
+                (value, start)
+            },
+        }
+    }
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = Tag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.skip_past("<");
+            let rest = self.rest();
+
+            if rest.is_empty() {
+                return None;
+            } else if rest.starts_with("!--") {
+                let start = self.cursor - 1;
+                self.skip_past("-->");
+                self.comments.push(start..self.cursor);
+            } else if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                let tag = self.start_tag();
+
+                if Tags::RAW_TEXT_ELEMENTS.contains(&tag.name.as_str()) {
+                    self.skip_raw_text(&tag.name);
+                }
+
+                return Some(tag);
+            } else if rest.starts_with(['/', '!', '?']) {
+                // end tags, doctypes and processing instructions
+                self.skip_past(">");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(src: &str) -> Vec<(String, &str)> {
+        html(src)
+            .map(|(href, span)| {
+                let text = &src[span.start().to_usize()..span.end().to_usize()];
+                (href, text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detect_common_links_in_html() {
+        let src = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <link rel="stylesheet" href="style.css">
+    <script src='./script.js'></script>
+  </head>
+  <body>
+    <a href="https://example.com/">Example</a>
+    <A HREF=../README.md#license>README</A>
+    <img src="img.png" alt="An image">
+    <video poster="poster.jpg"></video>
+    <form action="/submit"></form>
+  </body>
+</html>"#;
+        let should_be = vec![
+            (String::from("style.css"), Span::new(65, 74)),
+            (String::from("./script.js"), Span::new(94, 105)),
+            (String::from("https://example.com/"), Span::new(149, 169)),
+            (String::from("../README.md#license"), Span::new(195, 215)),
+            (String::from("img.png"), Span::new(241, 248)),
+            (String::from("poster.jpg"), Span::new(285, 295)),
+            (String::from("/submit"), Span::new(324, 331)),
+        ];
+
+        let got: Vec<_> = html(src).collect();
+
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn spans_point_at_the_url() {
+        let src = r#"<a class="x" href = " ./foo.html ">foo</a>"#;
+
+        let got = links(src);
+
+        assert_eq!(got, vec![(String::from("./foo.html"), "./foo.html")]);
+    }
+
+    #[test]
+    fn each_srcset_candidate_is_a_separate_link() {
+        let src =
+            r#"<img srcset="small.png 480w,  large.png 2x,no-descriptor.png">"#;
+
+        let got = links(src);
+
+        assert_eq!(
+            got,
+            vec![
+                (String::from("small.png"), "small.png"),
+                (String::from("large.png"), "large.png"),
+                (String::from("no-descriptor.png"), "no-descriptor.png"),
+            ]
+        );
+    }
+
+    #[test]
+    fn meta_refresh() {
+        let inputs = vec![
+            r#"<meta http-equiv="refresh" content="0; url=linkcheck/index.html" />"#,
+            r#"<meta content="5;URL='linkcheck/index.html'" http-equiv="Refresh">"#,
+            r#"<meta http-equiv="refresh" content="0, linkcheck/index.html">"#,
+        ];
+
+        for src in inputs {
+            let got = links(src);
+
+            assert_eq!(
+                got,
+                vec![(
+                    String::from("linkcheck/index.html"),
+                    "linkcheck/index.html"
+                )],
+                "{}",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn meta_refresh_with_non_ascii_content() {
+        let src = r#"<meta http-equiv="refresh" content="0; éé">"#;
+
+        let got = links(src);
+
+        assert_eq!(got, vec![(String::from("éé"), "éé")]);
+    }
+
+    #[test]
+    fn ignore_links_in_comments_and_scripts() {
+        let src = r#"
+<!-- <a href="commented-out.html"></a> -->
+<script>document.write('<a href="from-script.html">');</script>
+<style>a::after { content: '<img src="from-style.png">'; }</style>
+<a href="real.html">real</a>
+"#;
+
+        let got = links(src);
+
+        assert_eq!(got, vec![(String::from("real.html"), "real.html")]);
+    }
+
+    #[test]
+    fn record_the_title_attribute() {
+        let src = r#"<a title="Tom &amp; Jerry" href="tom.html">T</a>"#;
+
+        let got: Vec<_> = html_links(src).collect();
+
+        assert_eq!(got[0].kind, LinkKind::Html);
+        assert_eq!(got[0].title.as_deref(), Some("Tom & Jerry"));
+    }
+
+    #[test]
+    fn suppressed_links_are_flagged() {
+        let src = r#"
+<a href="flaky.html">flaky</a> <!-- linkcheck-ignore -->
+<a href="fine.html">fine</a>
+<script>// <!-- linkcheck-disable --></script>
+<a href="also-fine.html">also fine</a>
+"#;
+
+        let got: Vec<_> = html_links(src)
+            .map(|link| (link.href, link.suppressed))
+            .collect();
+
+        assert_eq!(
+            got,
+            vec![
+                (String::from("flaky.html"), true),
+                (String::from("fine.html"), false),
+                (String::from("also-fine.html"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn character_references_are_decoded() {
+        let src = r#"<a href="search?q=rust&amp;page=2&#x23;results">"#;
+
+        let got = links(src);
+
+        assert_eq!(
+            got,
+            vec![(
+                String::from("search?q=rust&page=2#results"),
+                "search?q=rust&amp;page=2&#x23;results"
+            )]
+        );
+    }
+}
@@ -1,5 +1,13 @@
+use crate::scanners::{
+    references::{unused_definitions, ReferenceLint},
+    suppress::{comments, Suppressions},
+    LinkKind, ScannedLink,
+};
 use codespan::Span;
-use pulldown_cmark::{BrokenLink, CowStr, Event, Options, Parser, Tag};
+use pulldown_cmark::{
+    BrokenLink, CowStr, Event, LinkType, Options, Parser, Tag,
+};
+use std::ops::Range;
 
 /// A scanner that uses [`pulldown_cmark`] to extract all links from markdown.
 ///
@@ -27,24 +35,215 @@ pub type BrokenLinkCallback<'src> = dyn FnMut(BrokenLink<'_>) -> std::option::Op
 
 /// A scanner that uses [`pulldown_cmark`] to extract all links from markdown,
 /// using the supplied callback to try and fix broken links.
+///
+/// [Suppressed links](crate::scanners) are skipped.
 pub fn markdown_with_broken_link_callback<'a>(
     src: &'a str,
     on_broken_link: Option<&'a mut BrokenLinkCallback<'a>>,
 ) -> impl Iterator<Item = (String, Span)> + 'a {
-    Parser::new_with_broken_link_callback(
+    scan(src, on_broken_link)
+        .links
+        .into_iter()
+        .filter(|link| !link.suppressed)
+        .map(|link| (link.href, link.span))
+}
+
+/// A scanner that extracts all links from markdown, including the ones which
+/// have been suppressed with a comment (see [`ScannedLink::suppressed`]).
+///
+/// # Examples
+///
+/// ```rust
+/// let src = "[a](https://example.com/) <!-- linkcheck-ignore -->\n[b](b.md)";
+///
+/// let got: Vec<_> = linkcheck::scanners::markdown_links(src).collect();
+///
+/// assert_eq!(got.len(), 2);
+/// assert!(got[0].suppressed);
+/// assert!(!got[1].suppressed);
+/// ```
+pub fn markdown_links(src: &str) -> impl Iterator<Item = ScannedLink> + '_ {
+    scan(src, None).links.into_iter()
+}
+
+/// Everything the markdown scanner found in a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownScan {
+    /// Every link in the document, including suppressed ones.
+    pub links: Vec<ScannedLink>,
+    /// Reference links with no definition and definitions which are never
+    /// used, in the order they appear.
+    pub lints: Vec<ReferenceLint>,
+}
+
+/// Scan a markdown document for links, also checking that every reference
+/// link (e.g. `[text][label]`) has a matching definition and every definition
+/// is used.
+///
+/// Lints in a region suppressed with a comment are skipped.
+///
+/// # Examples
+///
+/// ```rust
+/// # use codespan::Span;
+/// use linkcheck::scanners::ReferenceLint;
+///
+/// let src = "A [typo][lable].\n\n[label]: https://example.com/\n";
+///
+/// let got = linkcheck::scanners::markdown_with_lints(src);
+///
+/// assert!(got.links.is_empty());
+/// assert_eq!(
+///     got.lints,
+///     vec![
+///         ReferenceLint::Undefined {
+///             label: String::from("lable"),
+///             span: Span::new(2, 15),
+///         },
+///         ReferenceLint::Unused {
+///             label: String::from("label"),
+///             span: Span::new(18, 47),
+///         },
+///     ]
+/// );
+/// ```
+pub fn markdown_with_lints(src: &str) -> MarkdownScan { scan(src, None) }
+
+fn scan<'a>(
+    src: &'a str,
+    mut on_broken_link: Option<&'a mut BrokenLinkCallback<'a>>,
+) -> MarkdownScan {
+    let mut html_comments = Vec::new();
+    let mut links: Vec<ScannedLink> = Vec::new();
+    let mut lints = Vec::new();
+    // the links whose text we are in the middle of, innermost last
+    let mut open = Vec::new();
+
+    let mut callback = |broken: BrokenLink<'_>| {
+        // a shortcut link (e.g. "[label]") may just be text in brackets
+        if let LinkType::Reference | LinkType::Collapsed = broken.link_type {
+            lints.push(ReferenceLint::Undefined {
+                label: broken.reference.to_string(),
+                span: Span::new(
+                    broken.span.start as u32,
+                    broken.span.end as u32,
+                ),
+            });
+        }
+
+        match on_broken_link {
+            Some(ref mut on_broken_link) => on_broken_link(broken),
+            None => None,
+        }
+    };
+
+    let events = Parser::new_with_broken_link_callback(
         src,
         Options::ENABLE_FOOTNOTES,
-        on_broken_link,
+        Some(&mut callback),
     )
-    .into_offset_iter()
-    .filter_map(|(event, range)| match event {
-        Event::Start(Tag::Link(_, dest, _))
-        | Event::Start(Tag::Image(_, dest, _)) => Some((
-            dest.to_string(),
-            Span::new(range.start as u32, range.end as u32),
-        )),
-        _ => None,
-    })
+    .into_offset_iter();
+
+    for (event, range) in events {
+        match event {
+            Event::Start(Tag::Link(link_type, dest, title)) => {
+                let kind = match link_type {
+                    LinkType::Inline => LinkKind::Inline,
+                    LinkType::Autolink | LinkType::Email => LinkKind::Autolink,
+                    _ => LinkKind::Reference,
+                };
+                open.push(links.len());
+                links.push(scanned_link(
+                    src, range, kind, link_type, dest, title,
+                ));
+            },
+            Event::Start(Tag::Image(link_type, dest, title)) => {
+                open.push(links.len());
+                links.push(scanned_link(
+                    src,
+                    range,
+                    LinkKind::Image,
+                    link_type,
+                    dest,
+                    title,
+                ));
+            },
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..)) => {
+                open.pop();
+            },
+            Event::Text(text) | Event::Code(text) => {
+                for &index in &open {
+                    links[index]
+                        .text
+                        .get_or_insert_with(String::new)
+                        .push_str(&text);
+                }
+            },
+            Event::Html(html) => html_comments.extend(
+                comments(&html)
+                    .into_iter()
+                    .map(|c| range.start + c.start..range.start + c.end),
+            ),
+            _ => {},
+        }
+    }
+
+    let suppressions = Suppressions::new(src, html_comments);
+    for link in &mut links {
+        link.suppressed = suppressions.contains(link.span);
+    }
+
+    lints.extend(unused_definitions(src, &links));
+    lints.retain(|lint| !suppressions.contains(lint.span()));
+    lints.sort_by_key(|lint| lint.span().start());
+
+    MarkdownScan { links, lints }
+}
+
+fn scanned_link(
+    src: &str,
+    range: Range<usize>,
+    kind: LinkKind,
+    link_type: LinkType,
+    dest: CowStr<'_>,
+    title: CowStr<'_>,
+) -> ScannedLink {
+    let span = Span::new(range.start as u32, range.end as u32);
+    let mut link = ScannedLink::new(dest.to_string(), span, kind);
+
+    if !title.is_empty() {
+        link.title = Some(title.to_string());
+    }
+    link.reference = reference_label(&src[range], link_type);
+
+    link
+}
+
+/// Pull the label out of a reference-style link (e.g. `[text][label]`),
+/// because `pulldown-cmark` doesn't tell us which reference definition was
+/// used.
+fn reference_label(source: &str, link_type: LinkType) -> Option<String> {
+    // images start with a "!"
+    let source = source.strip_prefix('!').unwrap_or(source);
+
+    let label = match link_type {
+        LinkType::Reference | LinkType::ReferenceUnknown => {
+            let rest = source.strip_suffix(']')?;
+            &rest[rest.rfind('[')? + 1..]
+        },
+        LinkType::Collapsed
+        | LinkType::CollapsedUnknown
+        | LinkType::Shortcut
+        | LinkType::ShortcutUnknown => {
+            // the span for "[label][]" doesn't always include the "[]"
+            let rest = source.strip_prefix('[')?;
+            rest.strip_suffix("][]")
+                .or_else(|| rest.strip_suffix(']'))?
+        },
+        _ => return None,
+    };
+
+    Some(label.to_string())
 }
 
 #[cfg(test)]
@@ -74,8 +273,166 @@ mod tests {
         ];
 
         let got: Vec<_> =
-            markdown_with_broken_link_callback(src, Some(&mut |_| None)).collect();
+            markdown_with_broken_link_callback(src, Some(&mut |_| None))
+                .collect();
 
         assert_eq!(got, should_be);
     }
+
+    #[test]
+    fn record_how_each_link_was_written() {
+        let src = r#"
+An [inline](a.md "A") link, a [reference][b], a [c][] and a [d], an
+<https://e.com/> autolink and [![an *image*](f.png)](g.md).
+
+[b]: https://b.com/
+[c]: https://c.com/
+[d]: https://d.com/ "D"
+"#;
+
+        let got: Vec<_> = markdown_links(src)
+            .map(|link| {
+                (link.href, link.kind, link.text, link.title, link.reference)
+            })
+            .collect();
+
+        let s = |s: &str| Some(String::from(s));
+        assert_eq!(
+            got,
+            vec![
+                (
+                    String::from("a.md"),
+                    LinkKind::Inline,
+                    s("inline"),
+                    s("A"),
+                    None
+                ),
+                (
+                    String::from("https://b.com/"),
+                    LinkKind::Reference,
+                    s("reference"),
+                    None,
+                    s("b")
+                ),
+                (
+                    String::from("https://c.com/"),
+                    LinkKind::Reference,
+                    s("c"),
+                    None,
+                    s("c")
+                ),
+                (
+                    String::from("https://d.com/"),
+                    LinkKind::Reference,
+                    s("d"),
+                    s("D"),
+                    s("d")
+                ),
+                (
+                    String::from("https://e.com/"),
+                    LinkKind::Autolink,
+                    s("https://e.com/"),
+                    None,
+                    None
+                ),
+                (
+                    String::from("g.md"),
+                    LinkKind::Inline,
+                    s("an image"),
+                    None,
+                    None
+                ),
+                (
+                    String::from("f.png"),
+                    LinkKind::Image,
+                    s("an image"),
+                    None,
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_undefined_and_unused_references() {
+        let src = r#"
+A [link][Used], an ![image][img], a [missing][nowhere], a [gone][] and
+[brackets] which aren't a link.
+
+<!-- linkcheck-ignore-next-line -->
+[hidden][also-nowhere]
+
+[used]: https://example.com/
+[img]: img.png
+[unused]: https://unused.example.com/ "Title"
+[USED]: https://duplicate.example.com/
+"#;
+
+        let got: Vec<_> = markdown_with_lints(src)
+            .lints
+            .into_iter()
+            .map(|lint| {
+                (
+                    lint.to_string(),
+                    &src[lint.span().start().to_usize()
+                        ..lint.span().end().to_usize()],
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            got,
+            vec![
+                (
+                    String::from("There is no definition for \"nowhere\""),
+                    "[missing][nowhere]"
+                ),
+                (
+                    String::from("There is no definition for \"gone\""),
+                    "[gone]"
+                ),
+                (
+                    String::from("The definition for \"unused\" is never used"),
+                    r#"[unused]: https://unused.example.com/ "Title""#
+                ),
+                (
+                    String::from("The definition for \"USED\" is never used"),
+                    "[USED]: https://duplicate.example.com/"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn suppressed_links_are_flagged() {
+        let src = r#"
+<!-- linkcheck-ignore-next-line -->
+[flaky](https://flaky.example.com/)
+
+[fine](https://example.com/)
+
+<!-- linkcheck-disable -->
+[a](https://a.example.com/) and `<!-- linkcheck-enable -->`
+
+[b](https://b.example.com/)
+<!-- linkcheck-enable -->
+[c](https://c.example.com/)
+"#;
+
+        let got: Vec<_> = markdown_links(src)
+            .map(|link| (link.href, link.suppressed))
+            .collect();
+
+        assert_eq!(
+            got,
+            vec![
+                (String::from("https://flaky.example.com/"), true),
+                (String::from("https://example.com/"), false),
+                (String::from("https://a.example.com/"), true),
+                (String::from("https://b.example.com/"), true),
+                (String::from("https://c.example.com/"), false),
+            ]
+        );
+        assert_eq!(markdown(src).count(), 2);
+    }
 }
@@ -1,10 +1,129 @@
 //! A *scanner* is just a function that which can extract links from a body of
 //! text.
+//!
+//! The markdown and HTML scanners understand comments which suppress
+//! checking for individual links:
+//!
+//! - `<!-- linkcheck-ignore -->` suppresses links on the same line
+//! - `<!-- linkcheck-ignore-next-line -->` suppresses links on the next line
+//! - `<!-- linkcheck-disable -->` and `<!-- linkcheck-enable -->` suppress
+//!   everything between them (or until the end of the document)
+//!
+//! The [`markdown_links()`] and [`html_links()`] scanners report these as
+//! [`ScannedLink::suppressed`] so they can be listed as ignored, while the
+//! simpler scanners just skip them.
+//!
+//! The [`markdown_with_lints()`] scanner also reports reference links without
+//! a definition and definitions which are never used (see [`ReferenceLint`]).
 
+mod html;
 mod markdown;
 mod plaintext;
+mod references;
+mod suppress;
 
+pub use html::{html, html_links};
 pub use markdown::{
-    markdown, markdown_with_broken_link_callback, BrokenLinkCallback,
+    markdown, markdown_links, markdown_with_broken_link_callback,
+    markdown_with_lints, BrokenLinkCallback, MarkdownScan,
 };
 pub use plaintext::plaintext;
+pub use references::ReferenceLint;
+pub(crate) use references::find_definition;
+
+use crate::Link;
+use codespan::{FileId, Span};
+use std::{
+    ffi::OsString,
+    fmt::{self, Display, Formatter},
+};
+
+/// The different ways a link can be written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum LinkKind {
+    /// A markdown link with its destination written inline (e.g.
+    /// `[text](https://example.com/)`).
+    Inline,
+    /// A markdown link which refers to a reference definition (e.g.
+    /// `[text][label]`, `[label][]` or `[label]`).
+    Reference,
+    /// A markdown autolink (e.g. `<https://example.com/>`).
+    Autolink,
+    /// A markdown image (e.g. `![alt text](image.png)`), written either inline
+    /// or as a reference.
+    Image,
+    /// An attribute on a HTML element (e.g. `<a href="...">`).
+    Html,
+    /// A URL found in plain text.
+    PlainText,
+}
+
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LinkKind::Inline => "inline",
+            LinkKind::Reference => "reference",
+            LinkKind::Autolink => "autolink",
+            LinkKind::Image => "image",
+            LinkKind::Html => "html",
+            LinkKind::PlainText => "plain-text",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// A link found by a scanner.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ScannedLink {
+    /// The link itself.
+    pub href: String,
+    /// Where the link lies in its source text.
+    pub span: Span,
+    /// How the link was written.
+    pub kind: LinkKind,
+    /// The link's text (or an image's alt text), if it has any.
+    pub text: Option<String>,
+    /// The link's title (e.g. `[text](href "title")` or
+    /// `<a title="...">`), if it has one.
+    pub title: Option<String>,
+    /// The label of the reference definition a [`LinkKind::Reference`] link
+    /// (or an image written as a reference) refers to.
+    pub reference: Option<String>,
+    /// Was this link suppressed by a comment like
+    /// `<!-- linkcheck-ignore -->`?
+    pub suppressed: bool,
+}
+
+impl ScannedLink {
+    /// Create a new [`ScannedLink`].
+    pub fn new<S: Into<String>>(href: S, span: Span, kind: LinkKind) -> Self {
+        ScannedLink {
+            href: href.into(),
+            span,
+            kind,
+            text: None,
+            title: None,
+            reference: None,
+            suppressed: false,
+        }
+    }
+
+    /// Turn this into a [`Link`] belonging to a particular document.
+    pub fn into_link(self, file: FileId, file_name: OsString) -> Link {
+        let mut link = Link::new(self.href, self.span, file, file_name);
+        link.kind = Some(self.kind);
+        link.text = self.text;
+        link.title = self.title;
+        link.reference = self.reference;
+        link.suppressed = self.suppressed;
+        link
+    }
+}
@@ -0,0 +1,195 @@
+use crate::scanners::ScannedLink;
+use codespan::Span;
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Options, Parser};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+/// A problem with the way reference links and reference definitions are used
+/// in a markdown document.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ReferenceLint {
+    /// A reference link (e.g. `[text][label]`) whose label was never defined.
+    Undefined {
+        /// The label, as it was written.
+        label: String,
+        /// Where the link is.
+        span: Span,
+    },
+    /// A reference definition (e.g. `[label]: https://example.com/`) which
+    /// no link uses.
+    Unused {
+        /// The label, as it was written.
+        label: String,
+        /// Where the definition is.
+        span: Span,
+    },
+}
+
+impl ReferenceLint {
+    /// The label this lint is about.
+    pub fn label(&self) -> &str {
+        match self {
+            ReferenceLint::Undefined { label, .. }
+            | ReferenceLint::Unused { label, .. } => label,
+        }
+    }
+
+    /// Where the offending link or definition is.
+    pub fn span(&self) -> Span {
+        match *self {
+            ReferenceLint::Undefined { span, .. }
+            | ReferenceLint::Unused { span, .. } => span,
+        }
+    }
+}
+
+impl Display for ReferenceLint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferenceLint::Undefined { label, .. } => {
+                write!(f, "There is no definition for \"{}\"", label)
+            },
+            ReferenceLint::Unused { label, .. } => {
+                write!(f, "The definition for \"{}\" is never used", label)
+            },
+        }
+    }
+}
+
+/// Find every reference definition which none of the `links` refer to.
+///
+/// Only the first definition for a label is ever used, so duplicates are
+/// always reported. Definitions nested inside other blocks (e.g. a list or
+/// block quote) aren't detected.
+pub(crate) fn unused_definitions(
+    src: &str,
+    links: &[ScannedLink],
+) -> Vec<ReferenceLint> {
+    let mut used: HashSet<String> = links
+        .iter()
+        .filter_map(|link| link.reference.as_deref())
+        .map(normalize)
+        .collect();
+
+    definitions(src)
+        .into_iter()
+        // a label is "used up" by the first definition, which is the only
+        // one pulldown-cmark looks at
+        .filter(|(label, _)| !used.remove(&normalize(label)))
+        .map(|(label, range)| ReferenceLint::Unused {
+            label,
+            span: Span::new(range.start as u32, range.end as u32),
+        })
+        .collect()
+}
+
+/// Find the first top-level definition for a reference label (see
+/// [`crate::Link::reference`]).
+pub(crate) fn find_definition(src: &str, label: &str) -> Option<Range<usize>> {
+    let label = normalize(label);
+
+    definitions(src)
+        .into_iter()
+        .find(|(defined, _)| normalize(defined) == label)
+        .map(|(_, range)| range)
+}
+
+/// Find the label and location of every top-level reference definition.
+fn definitions(src: &str) -> Vec<(String, Range<usize>)> {
+    // pulldown-cmark doesn't emit events for reference definitions, so
+    // anything that looks like one and isn't part of another block must be a
+    // definition
+    lazy_static! {
+        static ref DEFINITION: Regex =
+            Regex::new(r"(?m)^ {0,3}(\[((?:[^\\\[\]]|\\.)+)\]:.*?)[ \t]*$")
+                .unwrap();
+    }
+    let blocks = top_level_blocks(src);
+
+    DEFINITION
+        .captures_iter(src)
+        .filter_map(|captures| {
+            let definition = captures.get(1)?;
+            let label = captures.get(2)?;
+
+            if blocks
+                .iter()
+                .any(|block| block.contains(&definition.start()))
+                || label.as_str().trim().is_empty()
+            {
+                None
+            } else {
+                Some((label.as_str().to_string(), definition.range()))
+            }
+        })
+        .collect()
+}
+
+fn top_level_blocks(src: &str) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut depth = 0_usize;
+
+    for (event, range) in
+        Parser::new_ext(src, Options::ENABLE_FOOTNOTES).into_offset_iter()
+    {
+        match event {
+            Event::Start(_) => {
+                if depth == 0 {
+                    blocks.push(range);
+                }
+                depth += 1;
+            },
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ if depth == 0 => blocks.push(range),
+            _ => {},
+        }
+    }
+
+    blocks
+}
+
+/// Labels are matched case-insensitively, ignoring extra whitespace.
+fn normalize(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_find_top_level_definitions() {
+        let src = "[a]: https://a.com/\n\
+                   \n\
+                   Some text\n\
+                   [b]: not a definition\n\
+                   \n\
+                   ```\n\
+                   [c]: https://c.com/\n\
+                   ```\n\
+                   \n   [D  e]: https://d.com/ \"title\"  \n";
+
+        let got: Vec<_> = definitions(src)
+            .into_iter()
+            .map(|(label, range)| (label, &src[range]))
+            .collect();
+
+        assert_eq!(
+            got,
+            vec![
+                (String::from("a"), "[a]: https://a.com/"),
+                (String::from("D  e"), "[D  e]: https://d.com/ \"title\""),
+            ]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use codespan::Span;
+use std::ops::Range;
+
+/// The parts of a document where links have been suppressed using comments
+/// like `<!-- linkcheck-ignore -->` (see the [`crate::scanners`] docs for
+/// every directive).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct Suppressions {
+    ranges: Vec<Range<usize>>,
+}
+
+impl Suppressions {
+    /// Find the suppressed regions, given the location of every comment
+    /// (including the `<!--` and `-->`).
+    pub(crate) fn new<I>(src: &str, comments: I) -> Self
+    where
+        I: IntoIterator<Item = Range<usize>>,
+    {
+        let mut ranges = Vec::new();
+        let mut disabled_at = None;
+
+        for comment in comments {
+            match directive(&src[comment.clone()]) {
+                Some("linkcheck-ignore") => ranges.push(
+                    line_start(src, comment.start)..line_end(src, comment.end),
+                ),
+                Some("linkcheck-ignore-next-line") => {
+                    let next_line =
+                        (line_end(src, comment.end) + 1).min(src.len());
+                    ranges.push(next_line..line_end(src, next_line));
+                },
+                Some("linkcheck-disable") => {
+                    disabled_at = disabled_at.or(Some(comment.end));
+                },
+                Some("linkcheck-enable") => {
+                    if let Some(start) = disabled_at.take() {
+                        ranges.push(start..comment.start);
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        if let Some(start) = disabled_at {
+            ranges.push(start..src.len());
+        }
+
+        Suppressions { ranges }
+    }
+
+    /// Has the link starting at this [`Span`] been suppressed?
+    pub(crate) fn contains(&self, span: Span) -> bool {
+        let start = span.start().to_usize();
+        self.ranges.iter().any(|range| range.contains(&start))
+    }
+}
+
+/// Find every `<!-- ... -->` comment in a chunk of HTML, with offsets relative
+/// to the start of `html`.
+pub(crate) fn comments(html: &str) -> Vec<Range<usize>> {
+    let mut comments = Vec::new();
+    let mut cursor = 0;
+
+    while let Some(start) = html[cursor..].find("<!--").map(|i| cursor + i) {
+        let end = html[start + 4..]
+            .find("-->")
+            .map(|i| start + 4 + i + 3)
+            .unwrap_or(html.len());
+        comments.push(start..end);
+        cursor = end;
+    }
+
+    comments
+}
+
+/// Get the directive inside a comment (e.g. `linkcheck-ignore`).
+fn directive(comment: &str) -> Option<&str> {
+    let inner = comment.strip_prefix("<!--")?;
+    let inner = inner.strip_suffix("-->").unwrap_or(inner);
+
+    Some(inner.trim())
+}
+
+fn line_start(src: &str, index: usize) -> usize {
+    src[..index].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+fn line_end(src: &str, index: usize) -> usize {
+    src[index..]
+        .find('\n')
+        .map(|i| index + i)
+        .unwrap_or(src.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_suppressed_regions() {
+        let src = "A <!-- linkcheck-ignore -->\n\
+                   B\n\
+                   <!-- linkcheck-ignore-next-line -->\n\
+                   C\n\
+                   D\n\
+                   <!-- linkcheck-disable -->\n\
+                   E\n\
+                   <!-- linkcheck-enable -->\n\
+                   F <!-- unrelated -->\n\
+                   <!--linkcheck-disable-->\n\
+                   G";
+        let suppressions = Suppressions::new(src, comments(src));
+        let at = |c: char| {
+            let index = src.find(c).unwrap() as u32;
+            Span::new(index, index + 1)
+        };
+
+        let suppressed: String = "ABCDEFG"
+            .chars()
+            .filter(|&c| suppressions.contains(at(c)))
+            .collect();
+
+        assert_eq!(suppressed, "ACEG");
+    }
+}
@@ -0,0 +1,288 @@
+//! Working out which anchors (the bit after a `#` in a link) a document
+//! defines.
+
+use crate::validation::{slug::unique_slug, Slugger, Warning};
+use kuchiki::{iter::NodeIterator, traits::TendrilSink, NodeRef};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use std::collections::{HashMap, HashSet};
+
+/// The anchors defined by a document.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Anchors {
+    /// Every anchor.
+    pub(crate) all: HashSet<String>,
+    /// Anchors which are only defined using `<a name="...">`, which is
+    /// obsolete in HTML5.
+    pub(crate) deprecated: HashSet<String>,
+}
+
+impl Anchors {
+    /// Combine the `id`s and `<a name="...">`s found in a document.
+    fn new(mut ids: HashSet<String>, names: HashSet<String>) -> Self {
+        // a name is fine as long as something also has it as an id
+        let deprecated = names.difference(&ids).cloned().collect();
+        ids.extend(names);
+
+        Anchors {
+            all: ids,
+            deprecated,
+        }
+    }
+
+    /// Find the anchor a fragment refers to.
+    ///
+    /// This returns `None` if nothing matches, otherwise it gives you any
+    /// [`Warning`]s about the match (e.g. because the anchor only matched
+    /// when case was ignored).
+    pub(crate) fn find(&self, fragment: &str) -> Option<Vec<Warning>> {
+        if self.all.contains(fragment) {
+            let mut warnings = Vec::new();
+            if self.deprecated.contains(fragment) {
+                warnings.push(Warning::DeprecatedAnchor {
+                    anchor: fragment.to_string(),
+                });
+            }
+            return Some(warnings);
+        }
+
+        let lowercase = fragment.to_lowercase();
+        let anchor = self
+            .all
+            .iter()
+            .filter(|anchor| anchor.to_lowercase() == lowercase)
+            .min()?;
+
+        Some(vec![Warning::CaseInsensitiveFragment {
+            anchor: anchor.clone(),
+        }])
+    }
+}
+
+/// Get every anchor defined in a HTML document (element `id`s and
+/// `<a name="...">`).
+pub(crate) fn html_anchors(document: &NodeRef) -> Anchors {
+    let mut ids = HashSet::new();
+    let mut names = HashSet::new();
+    find_html_anchors(document, &mut ids, &mut names);
+
+    Anchors::new(ids, names)
+}
+
+fn find_html_anchors(
+    document: &NodeRef,
+    ids: &mut HashSet<String>,
+    names: &mut HashSet<String>,
+) {
+    for element in document.descendants().elements() {
+        let attributes = element.attributes.borrow();
+
+        if let Some(id) = attributes.get("id") {
+            ids.insert(id.to_string());
+        }
+        if &*element.name.local == "a" {
+            if let Some(name) = attributes.get("name") {
+                names.insert(name.to_string());
+            }
+        }
+    }
+}
+
+/// Get every anchor defined in a markdown document.
+///
+/// This includes the IDs generated for each heading by the [`Slugger`] (or the
+/// explicit ID given with a `{#custom-id}` attribute), as well as any `id`s or
+/// `<a name="...">` anchors in inline HTML.
+pub(crate) fn markdown_anchors(src: &str, slugger: &dyn Slugger) -> Anchors {
+    let mut anchors = HashSet::new();
+    let mut names = HashSet::new();
+    let mut ids = HashMap::new();
+    let mut current_heading: Option<String> = None;
+
+    for event in Parser::new_ext(src, markdown_options()) {
+        match event {
+            Event::Start(Tag::Heading(_)) => {
+                current_heading = Some(String::new());
+            },
+            Event::End(Tag::Heading(_)) => {
+                let text = current_heading.take().unwrap_or_default();
+                let anchor = match split_heading_attributes(&text) {
+                    (_, Some(id)) => id.to_string(),
+                    (text, None) => unique_slug(slugger, text, &mut ids),
+                };
+                anchors.insert(anchor);
+            },
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = current_heading.as_mut() {
+                    heading.push_str(&text);
+                }
+            },
+            Event::Html(html) => {
+                let fragment = kuchiki::parse_html().one(html.as_ref());
+                find_html_anchors(&fragment, &mut anchors, &mut names);
+            },
+            _ => {},
+        }
+    }
+
+    Anchors::new(anchors, names)
+}
+
+/// The extensions `mdbook` enables when rendering markdown.
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+}
+
+/// Split the text of a heading like `"Some Heading {#custom-id .class}"` into
+/// the heading text and its explicit ID (if any).
+fn split_heading_attributes(text: &str) -> (&str, Option<&str>) {
+    let trimmed = text.trim_end();
+
+    let (before, attributes) =
+        match trimmed.strip_suffix('}').and_then(|rest| {
+            rest.rfind('{').map(|ix| (&rest[..ix], &rest[ix + 1..]))
+        }) {
+            Some(pair) => pair,
+            None => return (text, None),
+        };
+
+    let is_attribute = |word: &str| {
+        (word.starts_with('#') || word.starts_with('.')) && word.len() > 1
+    };
+
+    if attributes.split_whitespace().all(is_attribute) {
+        let id = attributes
+            .split_whitespace()
+            .filter_map(|word| word.strip_prefix('#'))
+            .next_back();
+        (before.trim_end(), id)
+    } else {
+        (text, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::MdbookSlugger;
+
+    fn anchors(src: &str) -> Vec<String> {
+        let mut anchors: Vec<_> = markdown_anchors(src, &MdbookSlugger)
+            .all
+            .into_iter()
+            .collect();
+        anchors.sort();
+        anchors
+    }
+
+    #[test]
+    fn atx_headings() {
+        let src = "# Getting Started\n\nSome text.\n\n## Using `linkcheck`\n";
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["getting-started", "using-linkcheck"]);
+    }
+
+    #[test]
+    fn ignore_hashes_in_fenced_code_blocks() {
+        let src = "# Heading\n\n```bash\n# not a heading\necho hi\n```\n";
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["heading"]);
+    }
+
+    #[test]
+    fn setext_headings() {
+        let src = "First Heading\n=============\n\nSecond Heading\n---\n";
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["first-heading", "second-heading"]);
+    }
+
+    #[test]
+    fn strip_the_closing_sequence_of_an_atx_heading() {
+        let src = "## Closed Heading ##\n";
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["closed-heading"]);
+    }
+
+    #[test]
+    fn explicit_heading_ids() {
+        let src = "# Some Heading {#custom-id}\n\n## Another {#other .class}\n";
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["custom-id", "other"]);
+    }
+
+    #[test]
+    fn braces_which_are_not_attributes_are_part_of_the_heading() {
+        let src = "# The {weird} Heading\n";
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["the-weird-heading"]);
+    }
+
+    #[test]
+    fn duplicate_headings_get_unique_ids() {
+        let src = "# Example\n\n# Example\n";
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["example", "example-1"]);
+    }
+
+    #[test]
+    fn inline_html_anchors() {
+        let src = r#"
+Some text with <a id="inline-id"></a> an anchor.
+
+<a name="named-anchor"></a>
+
+<div id="block-id">
+</div>
+"#;
+
+        let got = anchors(src);
+
+        assert_eq!(got, vec!["block-id", "inline-id", "named-anchor"]);
+    }
+
+    #[test]
+    fn anchors_only_defined_by_name_are_deprecated() {
+        let src = "# Heading\n\n<a name=\"old\"></a><a name=\"heading\"></a>\n";
+
+        let got = markdown_anchors(src, &MdbookSlugger);
+
+        assert_eq!(got.find("heading"), Some(Vec::new()));
+        assert_eq!(
+            got.find("old"),
+            Some(vec![Warning::DeprecatedAnchor {
+                anchor: String::from("old")
+            }])
+        );
+    }
+
+    #[test]
+    fn fragments_can_match_when_case_is_ignored() {
+        let got =
+            markdown_anchors("# Some Heading {#Custom-ID}\n", &MdbookSlugger);
+
+        assert_eq!(got.find("Custom-ID"), Some(Vec::new()));
+        assert_eq!(
+            got.find("custom-id"),
+            Some(vec![Warning::CaseInsensitiveFragment {
+                anchor: String::from("Custom-ID")
+            }])
+        );
+        assert_eq!(got.find("missing"), None);
+    }
+}
@@ -1,7 +1,15 @@
+use crate::validation::Warning;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
     time::{Duration, SystemTime},
 };
+#[cfg(feature = "serde-1")]
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+};
 use url::Url;
 
 /// A cache used to skip unnecessary network requests.
@@ -12,9 +20,91 @@ pub struct Cache {
 }
 
 impl Cache {
+    /// The version of the on-disk format used by [`Cache::save()`].
+    ///
+    /// This gets bumped whenever a [`CacheEntry`] changes, so old cache files
+    /// are discarded instead of being misinterpreted.
+    pub const SCHEMA_VERSION: u32 = 1;
+
     /// Create a new, empty [`Cache`].
     pub fn new() -> Self { Cache::default() }
 
+    /// Load a [`Cache`] previously written by [`Cache::save()`].
+    ///
+    /// A missing file gives you an empty [`Cache`]. The same goes for files
+    /// which are corrupt or were written using a different
+    /// [`Cache::SCHEMA_VERSION`], because it's always safe to throw away a
+    /// cache.
+    #[cfg(feature = "serde-1")]
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Cache> {
+        let path = path.as_ref();
+
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::debug!("\"{}\" doesn't exist yet", path.display());
+                return Ok(Cache::new());
+            },
+            Err(e) => return Err(e),
+        };
+
+        // check the version before trying to deserialize the entries because
+        // older versions may have a completely different structure
+        let parsed = serde_json::from_reader(BufReader::new(file)).and_then(
+            |file: CacheFile| {
+                if file.version == Cache::SCHEMA_VERSION {
+                    serde_json::from_value(file.cache).map(Some)
+                } else {
+                    Ok(None)
+                }
+            },
+        );
+
+        match parsed {
+            Ok(Some(cache)) => Ok(cache),
+            Ok(None) => {
+                log::warn!(
+                    "Ignoring \"{}\" because it uses a different version of the cache format",
+                    path.display(),
+                );
+                Ok(Cache::new())
+            },
+            Err(e) => {
+                log::warn!(
+                    "Ignoring \"{}\" because it is corrupt: {}",
+                    path.display(),
+                    e,
+                );
+                Ok(Cache::new())
+            },
+        }
+    }
+
+    /// Save the [`Cache`] to a file.
+    ///
+    /// The [`Cache`] is written to a temporary file which then replaces the
+    /// original, so readers will never see a half-written file.
+    #[cfg(feature = "serde-1")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_os_string();
+        temp.push(".tmp");
+        let temp = Path::new(&temp);
+
+        let file = CacheFileRef {
+            version: Cache::SCHEMA_VERSION,
+            cache: self,
+        };
+
+        let mut writer = BufWriter::new(File::create(temp)?);
+        serde_json::to_writer(&mut writer, &file)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        drop(writer);
+
+        std::fs::rename(temp, path)
+    }
+
     /// Lookup a particular [`CacheEntry`].
     pub fn lookup(&self, url: &Url) -> Option<&CacheEntry> {
         self.entries.get(url)
@@ -40,6 +130,34 @@ impl Cache {
         false
     }
 
+    /// Find a [`CacheEntry`] which is recent enough that we can reuse its
+    /// result instead of checking the [`Url`] again.
+    ///
+    /// Valid entries expire after `timeout`, while entries for links which
+    /// were broken expire after `negative_timeout`. Temporary failures (see
+    /// [`FailureKind::is_permanent()`]) are never reused.
+    pub fn lookup_unexpired(
+        &self,
+        url: &Url,
+        timeout: Duration,
+        negative_timeout: Duration,
+    ) -> Option<&CacheEntry> {
+        let entry = self.lookup(url)?;
+        let age = entry.timestamp.elapsed().ok()?;
+
+        let timeout = match entry.failure {
+            _ if entry.valid => timeout,
+            Some(failure) if failure.is_permanent() => negative_timeout,
+            _ => return None,
+        };
+
+        if age < timeout {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
     /// Iterate over all known [`CacheEntries`][CacheEntry], regardless of
     /// whether they are stale or invalid.
     pub fn iter(&self) -> impl Iterator<Item = (&Url, &CacheEntry)> + '_ {
@@ -56,9 +174,25 @@ impl Extend<(Url, CacheEntry)> for Cache {
     }
 }
 
+/// The on-disk representation of a [`Cache`].
+#[cfg(feature = "serde-1")]
+#[derive(serde::Deserialize)]
+struct CacheFile {
+    version: u32,
+    #[serde(default)]
+    cache: serde_json::Value,
+}
+
+#[cfg(feature = "serde-1")]
+#[derive(serde::Serialize)]
+struct CacheFileRef<'a> {
+    version: u32,
+    cache: &'a Cache,
+}
+
 /// A timestamped boolean used by the [`Cache`] to keep track of the last time
 /// a web [`crate::Link`] was checked.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
 pub struct CacheEntry {
     /// When the [`CacheEntry`] was created.
@@ -66,11 +200,199 @@ pub struct CacheEntry {
     /// Did we find a valid resource the last time this [`crate::Link`] was
     /// checked?
     pub valid: bool,
+    /// Why the [`crate::Link`] was broken, if it was.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub failure: Option<FailureKind>,
+    /// The `ETag` header sent by the server, used to check whether a stale
+    /// entry is still valid with an `If-None-Match` request.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub etag: Option<String>,
+    /// The `Last-Modified` header sent by the server, used to check whether a
+    /// stale entry is still valid with an `If-Modified-Since` request.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub last_modified: Option<String>,
+    /// Every anchor defined by the page, if we've downloaded it.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub anchors: Option<HashSet<String>>,
+    /// The anchors which are only defined using the obsolete
+    /// `<a name="...">`, if we've downloaded the page.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub deprecated_anchors: Option<HashSet<String>>,
+    /// Any [`Warning`]s raised the last time the [`crate::Link`] was checked.
+    #[cfg_attr(feature = "serde-1", serde(default))]
+    pub warnings: Vec<Warning>,
 }
 
 impl CacheEntry {
     /// Create a new [`CacheEntry`].
     pub const fn new(timestamp: SystemTime, valid: bool) -> Self {
-        CacheEntry { timestamp, valid }
+        CacheEntry {
+            timestamp,
+            valid,
+            failure: None,
+            etag: None,
+            last_modified: None,
+            anchors: None,
+            deprecated_anchors: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Create a [`CacheEntry`] for a [`crate::Link`] which was broken.
+    pub const fn failed(timestamp: SystemTime, failure: FailureKind) -> Self {
+        CacheEntry {
+            timestamp,
+            valid: false,
+            failure: Some(failure),
+            etag: None,
+            last_modified: None,
+            anchors: None,
+            deprecated_anchors: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+/// A summary of why a web [`crate::Link`] was broken, so it can be stored in
+/// the [`Cache`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum FailureKind {
+    /// The server responded with an error status code.
+    Status(u16),
+    /// The request timed out.
+    Timeout,
+    /// We couldn't connect to the server.
+    Connect,
+    /// The page exists, but it doesn't contain the anchor.
+    MissingAnchor,
+    /// Something else went wrong.
+    Other,
+}
+
+impl FailureKind {
+    /// Is this failure likely to happen again if we re-check the
+    /// [`crate::Link`]?
+    ///
+    /// Client errors like `404 Not Found` and missing anchors are
+    /// permanent, while timeouts, `429 Too Many Requests` and server errors
+    /// are usually temporary.
+    pub fn is_permanent(self) -> bool {
+        match self {
+            FailureKind::Status(status) => {
+                (400..500).contains(&status) && status != 408 && status != 429
+            },
+            FailureKind::MissingAnchor => true,
+            FailureKind::Timeout
+            | FailureKind::Connect
+            | FailureKind::Other => false,
+        }
+    }
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::Status(status) => write!(f, "HTTP status {}", status),
+            FailureKind::Timeout => write!(f, "the request timed out"),
+            FailureKind::Connect => write!(f, "unable to connect"),
+            FailureKind::MissingAnchor => write!(f, "the anchor doesn't exist"),
+            FailureKind::Other => write!(f, "the request failed"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde-1"))]
+mod tests {
+    use super::*;
+
+    fn cache() -> Cache {
+        let mut cache = Cache::new();
+        cache.insert(
+            "https://example.com/".parse().unwrap(),
+            CacheEntry::new(SystemTime::UNIX_EPOCH, true),
+        );
+        cache.insert(
+            "https://example.com/page".parse().unwrap(),
+            CacheEntry {
+                etag: Some(String::from("\"abc\"")),
+                anchors: Some(
+                    vec![String::from("intro")].into_iter().collect(),
+                ),
+                ..CacheEntry::new(SystemTime::UNIX_EPOCH, true)
+            },
+        );
+        cache.insert(
+            "https://example.com/missing".parse().unwrap(),
+            CacheEntry::failed(
+                SystemTime::UNIX_EPOCH,
+                FailureKind::Status(404),
+            ),
+        );
+        cache
+    }
+
+    #[test]
+    fn only_reuse_permanent_failures_until_the_negative_timeout() {
+        let hour = Duration::from_secs(60 * 60);
+        let checked = SystemTime::now() - 2 * hour;
+        let mut cache = Cache::new();
+        let valid: Url = "https://example.com/valid".parse().unwrap();
+        let dead: Url = "https://example.com/dead".parse().unwrap();
+        let flaky: Url = "https://example.com/flaky".parse().unwrap();
+        cache.insert(valid.clone(), CacheEntry::new(checked, true));
+        cache.insert(
+            dead.clone(),
+            CacheEntry::failed(checked, FailureKind::Status(404)),
+        );
+        cache.insert(
+            flaky.clone(),
+            CacheEntry::failed(checked, FailureKind::Status(503)),
+        );
+
+        assert!(cache.lookup_unexpired(&valid, 3 * hour, hour).is_some());
+        assert!(cache.lookup_unexpired(&dead, 3 * hour, hour).is_none());
+        assert!(cache.lookup_unexpired(&dead, hour, 3 * hour).is_some());
+        assert!(cache.lookup_unexpired(&flaky, 3 * hour, 3 * hour).is_none());
+    }
+
+    #[test]
+    fn save_and_load_a_cache() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("cache.json");
+        let original = cache();
+
+        original.save(&path).unwrap();
+        let got = Cache::load(&path).unwrap();
+
+        assert_eq!(got, original);
+        assert!(!temp.path().join("cache.json.tmp").exists());
+    }
+
+    #[test]
+    fn missing_files_give_an_empty_cache() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let got = Cache::load(temp.path().join("cache.json")).unwrap();
+
+        assert_eq!(got, Cache::new());
+    }
+
+    #[test]
+    fn discard_corrupt_or_outdated_caches() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("cache.json");
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert_eq!(Cache::load(&path).unwrap(), Cache::new());
+
+        std::fs::write(&path, r#"{"version": 0, "cache": {"entries": 42}}"#)
+            .unwrap();
+        assert_eq!(Cache::load(&path).unwrap(), Cache::new());
     }
 }
@@ -1,9 +1,16 @@
 use crate::{
-    validation::{Cache, Options},
+    validation::{
+        Cache, HostLimits, IgnoreRules, MethodPolicy, Options, RetryPolicy,
+        Severity, Warning, WarningKind,
+    },
     Link,
 };
-use reqwest::{header::HeaderMap, Client, Url};
+use reqwest::{
+    header::HeaderMap, redirect::Policy, Client, ClientBuilder, Url,
+};
 use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
     sync::{Mutex, MutexGuard},
     time::Duration,
 };
@@ -12,6 +19,11 @@ use std::{
 /// process.
 pub trait Context {
     /// The HTTP client to use.
+    ///
+    /// Redirects are followed (and reported as [`crate::validation::Warning`]s
+    /// when they are permanent) by the validation process itself, so the
+    /// client should be created with [`reqwest::redirect::Policy::none()`].
+    /// If the client follows redirects on its own, they won't be noticed.
     fn client(&self) -> &Client;
 
     /// Options to use when checking a link on the filesystem.
@@ -20,6 +32,18 @@ pub trait Context {
     /// Get any extra headers that should be sent when checking this [`Url`].
     fn url_specific_headers(&self, _url: &Url) -> HeaderMap { HeaderMap::new() }
 
+    /// Which HTTP method(s) should be used when checking this [`Url`]?
+    ///
+    /// Some servers (e.g. S3 pre-signed URLs and some CDNs) reject `HEAD`
+    /// requests, so by default we'll fall back to `GET`. You can override
+    /// this on a per-host basis by inspecting [`Url::host_str()`].
+    fn method_policy(&self, _url: &Url) -> MethodPolicy {
+        MethodPolicy::default()
+    }
+
+    /// How should failed requests to this [`Url`] be retried?
+    fn retry_policy(&self, _url: &Url) -> RetryPolicy { RetryPolicy::default() }
+
     /// An optional cache that can be used to avoid unnecessary network
     /// requests.
     ///
@@ -27,11 +51,16 @@ pub trait Context {
     /// concurrently. This [`MutexGuard`] is guaranteed to be short lived (just
     /// the duration of a [`Cache::insert()`] or [`Cache::lookup()`]), so it's
     /// okay to use a [`std::sync::Mutex`] instead of [`futures::lock::Mutex`].
-    fn cache(&self) -> Option<MutexGuard<Cache>> { None }
+    fn cache(&self) -> Option<MutexGuard<'_, Cache>> { None }
 
-    /// How many items should we check at a time?
+    /// How many web requests may be in flight at a time, across all hosts?
     fn concurrency(&self) -> usize { 64 }
 
+    /// How many requests can we send to this host?
+    ///
+    /// This is called once per host for each call to [`crate::validate()`].
+    fn host_limits(&self, _host: &str) -> HostLimits { HostLimits::default() }
+
     /// How long should a cached item be considered valid for before we need to
     /// check again?
     fn cache_timeout(&self) -> Duration {
@@ -39,8 +68,43 @@ pub trait Context {
         Duration::from_secs(24 * 60 * 60)
     }
 
+    /// How long should we keep reporting a link as broken before checking it
+    /// again?
+    ///
+    /// This only applies to permanent failures (see
+    /// [`crate::validation::FailureKind::is_permanent()`]), so temporary
+    /// network problems are always re-checked.
+    fn negative_cache_timeout(&self) -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
     /// Should this [`Link`] be skipped?
     fn should_ignore(&self, _link: &Link) -> bool { false }
+
+    /// Work out why a [`Link`] should be skipped, if it should be.
+    ///
+    /// The `document` is the path of the file containing the link. By default
+    /// this defers to [`Context::should_ignore()`].
+    fn ignore_reason(&self, link: &Link, _document: &Path) -> Option<String> {
+        if self.should_ignore(link) {
+            Some(String::from("Context::should_ignore() said so"))
+        } else {
+            None
+        }
+    }
+
+    /// How long can a web request take before we raise a
+    /// [`Warning::SlowResponse`]?
+    fn slow_response_threshold(&self) -> Duration { Duration::from_secs(10) }
+
+    /// Decide whether a [`Warning`] about a [`Link`] should be reported as a
+    /// warning, treated as an error, or ignored.
+    ///
+    /// By default this is the [`WarningKind::default_severity()`], so most
+    /// warnings let CI builds annotate suspicious links without failing.
+    fn severity(&self, _link: &Link, warning: &Warning) -> Severity {
+        warning.kind().default_severity()
+    }
 }
 
 /// A basic [`Context`] implementation which uses all the defaults.
@@ -50,6 +114,16 @@ pub struct BasicContext {
     pub options: Options,
     client: Client,
     cache: Mutex<Cache>,
+    #[cfg_attr(not(feature = "serde-1"), allow(dead_code))]
+    cache_file: Option<PathBuf>,
+    severities: HashMap<WarningKind, Severity>,
+    slow_response_threshold: Duration,
+    ignore_rules: IgnoreRules,
+    headers: HashMap<String, HeaderMap>,
+    concurrency: usize,
+    host_limits: HashMap<String, HostLimits>,
+    cache_timeout: Duration,
+    negative_cache_timeout: Duration,
 }
 
 impl BasicContext {
@@ -63,6 +137,135 @@ impl BasicContext {
             client,
             options: Options::default(),
             cache: Mutex::new(Cache::new()),
+            cache_file: None,
+            severities: HashMap::new(),
+            slow_response_threshold: Duration::from_secs(10),
+            ignore_rules: IgnoreRules::new(),
+            headers: HashMap::new(),
+            concurrency: 64,
+            host_limits: HashMap::new(),
+            cache_timeout: Duration::from_secs(24 * 60 * 60),
+            negative_cache_timeout: Duration::from_secs(60 * 60),
+        }
+    }
+
+    /// A [`ClientBuilder`] with the settings used by
+    /// [`BasicContext::default()`], so you can tweak it before calling
+    /// [`BasicContext::with_client()`].
+    pub fn client_builder() -> ClientBuilder {
+        Client::builder()
+            .user_agent(BasicContext::USER_AGENT)
+            .redirect(Policy::none())
+    }
+
+    /// The [`IgnoreRules`] used to decide which links shouldn't be checked.
+    pub fn ignore_rules(&self) -> &IgnoreRules { &self.ignore_rules }
+
+    /// Set the [`IgnoreRules`] used to decide which links shouldn't be
+    /// checked.
+    ///
+    /// Globs on the document's path are matched relative to the
+    /// [`Options::root_directory()`], if there is one.
+    pub fn set_ignore_rules(mut self, rules: IgnoreRules) -> Self {
+        self.ignore_rules = rules;
+        self
+    }
+
+    /// Send these headers with every request to a particular host.
+    pub fn set_host_headers<S: Into<String>>(
+        mut self,
+        host: S,
+        headers: HeaderMap,
+    ) -> Self {
+        self.headers.insert(host.into(), headers);
+        self
+    }
+
+    /// Set how many web requests may be in flight at a time, across all
+    /// hosts.
+    pub fn set_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Override the [`HostLimits`] for a particular host.
+    pub fn set_host_limits<S: Into<String>>(
+        mut self,
+        host: S,
+        limits: HostLimits,
+    ) -> Self {
+        self.host_limits.insert(host.into(), limits);
+        self
+    }
+
+    /// Set how long a cached item is considered valid for.
+    pub fn set_cache_timeout(mut self, timeout: Duration) -> Self {
+        self.cache_timeout = timeout;
+        self
+    }
+
+    /// Set how long a link which is permanently broken keeps being reported
+    /// as broken before it is checked again.
+    pub fn set_negative_cache_timeout(mut self, timeout: Duration) -> Self {
+        self.negative_cache_timeout = timeout;
+        self
+    }
+
+    /// Set how seriously a particular kind of [`Warning`] should be taken.
+    ///
+    /// Anything without an explicit [`Severity`] uses the
+    /// [`WarningKind::default_severity()`].
+    pub fn set_severity(
+        mut self,
+        kind: WarningKind,
+        severity: Severity,
+    ) -> Self {
+        self.severities.insert(kind, severity);
+        self
+    }
+
+    /// Set how long a web request can take before we raise a
+    /// [`Warning::SlowResponse`].
+    pub fn set_slow_response_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_response_threshold = threshold;
+        self
+    }
+
+    /// Create a [`BasicContext`] which is backed by a cache file, so links
+    /// checked in previous runs can be skipped.
+    ///
+    /// Call [`BasicContext::save_cache()`] once validation is complete to
+    /// write the updated [`Cache`] back to disk.
+    #[cfg(feature = "serde-1")]
+    pub fn with_cache_file<P: Into<PathBuf>>(
+        cache_file: P,
+    ) -> std::io::Result<Self> {
+        BasicContext::default().set_cache_file(cache_file)
+    }
+
+    /// Load the [`Cache`] from a file, remembering where it came from so
+    /// [`BasicContext::save_cache()`] can write it back.
+    #[cfg(feature = "serde-1")]
+    pub fn set_cache_file<P: Into<PathBuf>>(
+        mut self,
+        cache_file: P,
+    ) -> std::io::Result<Self> {
+        let cache_file = cache_file.into();
+        self.cache = Mutex::new(Cache::load(&cache_file)?);
+        self.cache_file = Some(cache_file);
+
+        Ok(self)
+    }
+
+    /// Write the [`Cache`] to the file passed to
+    /// [`BasicContext::with_cache_file()`], if there was one.
+    #[cfg(feature = "serde-1")]
+    pub fn save_cache(&self) -> std::io::Result<()> {
+        match self.cache_file {
+            Some(ref path) => {
+                self.cache.lock().expect("Mutex was poisoned").save(path)
+            },
+            None => Ok(()),
         }
     }
 
@@ -74,8 +277,7 @@ impl BasicContext {
 
 impl Default for BasicContext {
     fn default() -> Self {
-        let client = Client::builder()
-            .user_agent(BasicContext::USER_AGENT)
+        let client = BasicContext::client_builder()
             .build()
             .expect("Unable to initialize the client");
 
@@ -88,7 +290,57 @@ impl Context for BasicContext {
 
     fn filesystem_options(&self) -> &Options { &self.options }
 
-    fn cache(&self) -> Option<MutexGuard<Cache>> {
+    fn url_specific_headers(&self, url: &Url) -> HeaderMap {
+        url.host_str()
+            .and_then(|host| self.headers.get(host))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn cache(&self) -> Option<MutexGuard<'_, Cache>> {
         Some(self.cache.lock().expect("Mutex was poisoned"))
     }
+
+    fn concurrency(&self) -> usize { self.concurrency }
+
+    fn host_limits(&self, host: &str) -> HostLimits {
+        self.host_limits.get(host).copied().unwrap_or_default()
+    }
+
+    fn cache_timeout(&self) -> Duration { self.cache_timeout }
+
+    fn negative_cache_timeout(&self) -> Duration { self.negative_cache_timeout }
+
+    fn should_ignore(&self, link: &Link) -> bool {
+        self.ignore_reason(link, Path::new(&link.file_name))
+            .is_some()
+    }
+
+    fn ignore_reason(&self, link: &Link, document: &Path) -> Option<String> {
+        // the root directory is always canonical, so the document needs to be
+        // too (e.g. "./docs/README.md") before we can strip the root from it
+        let canonical = self
+            .options
+            .root_directory()
+            .and_then(|_| dunce::canonicalize(document).ok());
+        let document = canonical.as_deref().unwrap_or(document);
+        let document = self
+            .options
+            .root_directory()
+            .and_then(|root| document.strip_prefix(root).ok())
+            .unwrap_or(document);
+
+        self.ignore_rules.check(link, document)
+    }
+
+    fn slow_response_threshold(&self) -> Duration {
+        self.slow_response_threshold
+    }
+
+    fn severity(&self, _link: &Link, warning: &Warning) -> Severity {
+        self.severities
+            .get(&warning.kind())
+            .copied()
+            .unwrap_or_else(|| warning.kind().default_severity())
+    }
 }
@@ -1,7 +1,8 @@
-use crate::validation::{Context, Reason};
-use lazy_static::lazy_static;
-use mdbook::utils::unique_id_from_content;
-use regex::Regex;
+use crate::validation::{
+    anchors::{html_anchors, markdown_anchors, Anchors},
+    escalate_warnings, Context, MdbookSlugger, Reason, Slugger, Warning,
+};
+use kuchiki::traits::TendrilSink;
 use std::{
     collections::HashMap,
     ffi::{OsStr, OsString},
@@ -56,36 +57,63 @@ pub fn resolve_link(
 ) -> Result<PathBuf, Reason> {
     let joined = options.join(current_directory, link)?;
 
-    let candidates = options.possible_names(joined);
+    let candidates: Vec<_> =
+        options.possible_names(joined).into_iter().collect();
 
-    for candidate in candidates {
+    for candidate in &candidates {
         log::trace!(
             "Checking if \"{}\" points to \"{}\"",
             link.display(),
             candidate.display(),
         );
 
-        if let Ok(canonical) = options.canonicalize(&candidate) {
+        if let Ok(canonical) = options.canonicalize(candidate) {
             options.sanity_check(&canonical)?;
             return Ok(canonical);
         }
     }
 
     log::trace!("None of the candidates exist for \"{}\"", link.display());
-    Err(Reason::Io(io::ErrorKind::NotFound.into()))
+    Err(Reason::NotFound { tried: candidates })
 }
 
 /// Check whether a [`Path`] points to a valid file on disk.
 ///
 /// If a fragment specifier is provided, this function will scan through the
-/// linked document and check that the file contains the corresponding anchor
-/// (e.g. markdown heading or HTML `id`).
+/// linked document and check that the file contains the corresponding anchor.
+/// HTML files (`*.html`, `*.htm` and `*.xhtml`) are searched for a matching
+/// `id` attribute or `<a name="...">`, while anything else is treated as
+/// markdown and checked for a matching heading.
+///
+/// A [`Warning`] only makes the link invalid when the
+/// [`Context::severity()`] says it is an error.
 pub fn check_filesystem<C>(
     current_directory: &Path,
     path: &Path,
     fragment: Option<&str>,
     ctx: &C,
 ) -> Result<(), Reason>
+where
+    C: Context + ?Sized,
+{
+    let warnings =
+        check_filesystem_with_warnings(current_directory, path, fragment, ctx)?;
+    let href = match fragment {
+        Some(fragment) => format!("{}#{}", path.display(), fragment),
+        None => path.display().to_string(),
+    };
+
+    escalate_warnings(&href, warnings, ctx)
+}
+
+/// The same as [`check_filesystem()`], except any [`Warning`]s about the link
+/// are returned.
+pub(crate) fn check_filesystem_with_warnings<C>(
+    current_directory: &Path,
+    path: &Path,
+    fragment: Option<&str>,
+    ctx: &C,
+) -> Result<Vec<Warning>, Reason>
 where
     C: Context + ?Sized,
 {
@@ -104,9 +132,14 @@ where
         resolved_location.display()
     );
 
+    let mut warnings = Vec::new();
+
     if let Some(fragment) = fragment {
-        let source = std::fs::read_to_string(&resolved_location.as_path())?;
-        check_fragment_in_md_source(&source, fragment)?;
+        let source = std::fs::read_to_string(resolved_location.as_path())?;
+
+        warnings = file_anchors(&resolved_location, &source, options)
+            .find(fragment)
+            .ok_or(Reason::File)?;
     }
 
     if let Err(reason) =
@@ -120,23 +153,30 @@ where
         return Err(reason);
     }
 
-    Ok(())
+    Ok(warnings)
 }
 
-fn check_fragment_in_md_source(
+/// Get the anchors defined by a file, using its extension to decide whether
+/// it should be parsed as HTML or markdown.
+pub(crate) fn file_anchors(
+    path: &Path,
     source: &str,
-    fragment: &str,
-) -> Result<(), Reason> {
-    lazy_static! {
-        static ref HEADING: Regex = Regex::new("(?m)^#+ (.+)").unwrap();
-    }
-    let mut map = HashMap::new();
-    for heading in HEADING.captures_iter(source) {
-        if unique_id_from_content(&heading[1], &mut map) == fragment {
-            return Ok(());
-        }
+    options: &Options,
+) -> Anchors {
+    if is_html(path) {
+        html_anchors(&kuchiki::parse_html().one(source))
+    } else {
+        markdown_anchors(source, options.slugger())
+    }
+}
+
+fn is_html(path: &Path) -> bool {
+    match path.extension().and_then(OsStr::to_str) {
+        Some(ext) => ["html", "htm", "xhtml"]
+            .iter()
+            .any(|html| ext.eq_ignore_ascii_case(html)),
+        None => false,
     }
-    Err(Reason::File)
 }
 
 /// Options to be used with [`resolve_link()`].
@@ -153,10 +193,17 @@ pub struct Options {
     // Note: the key is normalised to lowercase to make sure extensions are
     // case insensitive
     alternate_extensions: HashMap<String, Vec<OsString>>,
-    #[serde(skip, default = "nop_custom_validation")]
-    custom_validation: Arc<dyn Fn(&Path, Option<&str>) -> Result<(), Reason>>,
+    #[cfg_attr(
+        feature = "serde-1",
+        serde(skip, default = "nop_custom_validation")
+    )]
+    custom_validation: Arc<CustomValidation>,
+    #[cfg_attr(feature = "serde-1", serde(skip, default = "default_slugger"))]
+    slugger: Arc<dyn Slugger>,
 }
 
+type CustomValidation = dyn Fn(&Path, Option<&str>) -> Result<(), Reason>;
+
 impl Options {
     /// The name used by [`Options::default_file()`].
     pub const DEFAULT_FILE: &'static str = "index.html";
@@ -166,8 +213,7 @@ impl Options {
     pub fn default_alternate_extensions(
     ) -> impl IntoIterator<Item = (OsString, impl IntoIterator<Item = OsString>)>
     {
-        const MAPPING: &'static [(&'static str, &'static [&'static str])] =
-            &[("md", &["html"])];
+        const MAPPING: &[(&str, &[&str])] = &[("md", &["html"])];
 
         MAPPING.iter().map(|(ext, alts)| {
             (OsString::from(ext), alts.iter().map(OsString::from))
@@ -185,17 +231,18 @@ impl Options {
                 .map(|(key, values)| {
                     (
                         key.to_string_lossy().to_lowercase(),
-                        values.into_iter().map(Into::into).collect(),
+                        values.into_iter().collect(),
                     )
                 })
                 .collect(),
             custom_validation: nop_custom_validation(),
+            slugger: default_slugger(),
         }
     }
 
     /// Get the root directory, if one was provided.
     pub fn root_directory(&self) -> Option<&Path> {
-        self.root_directory.as_ref().map(|p| &**p)
+        self.root_directory.as_deref()
     }
 
     /// Set the [`Options::root_directory()`], automatically converting to its
@@ -284,6 +331,24 @@ impl Options {
         }
     }
 
+    /// The [`Slugger`] used to generate the anchors for headings in a
+    /// markdown document.
+    ///
+    /// This defaults to the [`MdbookSlugger`].
+    pub fn slugger(&self) -> &dyn Slugger { &*self.slugger }
+
+    /// Set the [`Options::slugger()`], so fragments are checked using the same
+    /// anchors as whatever will be rendering your markdown.
+    pub fn set_slugger<S>(self, slugger: S) -> Self
+    where
+        S: Slugger + 'static,
+    {
+        Options {
+            slugger: Arc::new(slugger),
+            ..self
+        }
+    }
+
     fn join(
         &self,
         current_dir: &Path,
@@ -408,10 +473,9 @@ impl Options {
     }
 }
 
-fn nop_custom_validation(
-) -> Arc<dyn Fn(&Path, Option<&str>) -> Result<(), Reason>> {
-    Arc::new(|_, _| Ok(()))
-}
+fn nop_custom_validation() -> Arc<CustomValidation> { Arc::new(|_, _| Ok(())) }
+
+fn default_slugger() -> Arc<dyn Slugger> { Arc::new(MdbookSlugger) }
 
 impl Default for Options {
     fn default() -> Self {
@@ -427,6 +491,7 @@ impl Debug for Options {
             links_may_traverse_the_root_directory,
             alternate_extensions,
             custom_validation: _,
+            slugger,
         } = self;
 
         f.debug_struct("Options")
@@ -437,6 +502,7 @@ impl Debug for Options {
                 links_may_traverse_the_root_directory,
             )
             .field("alternate_extensions", alternate_extensions)
+            .field("slugger", slugger)
             .finish()
     }
 }
@@ -449,6 +515,7 @@ impl PartialEq for Options {
             links_may_traverse_the_root_directory,
             alternate_extensions,
             custom_validation: _,
+            slugger: _,
         } = self;
 
         root_directory == &other.root_directory
@@ -461,7 +528,7 @@ impl PartialEq for Options {
 
 fn remove_absolute_components(
     path: &Path,
-) -> impl Iterator<Item = Component> + '_ {
+) -> impl Iterator<Item = Component<'_>> + '_ {
     path.components()
         .skip_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
 }
@@ -469,7 +536,10 @@ fn remove_absolute_components(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::BasicContext;
+    use crate::{
+        validation::{GitHubSlugger, GitLabSlugger, Severity, WarningKind},
+        BasicContext,
+    };
     use std::sync::atomic::{AtomicBool, Ordering};
 
     fn validation_dir() -> PathBuf {
@@ -592,6 +662,12 @@ mod tests {
         let err = resolve_link(&temp, link, &options).unwrap_err();
 
         assert!(err.file_not_found());
+        match err {
+            Reason::NotFound { tried } => {
+                assert_eq!(tried, vec![temp.join("bar")])
+            },
+            other => panic!("Expected NotFound, found {:?}", other),
+        }
     }
 
     #[test]
@@ -643,6 +719,194 @@ mod tests {
         assert_eq!(got, temp.join("index.html"));
     }
 
+    #[test]
+    fn fragments_in_html_files_use_ids_and_named_anchors() {
+        init_logging();
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::write(
+            temp.join("page.html"),
+            r#"<html><body>
+                <h1 id="section">Section</h1>
+                <a name="legacy-anchor"></a>
+                <p>Some text mentioning #not-an-anchor</p>
+            </body></html>"#,
+        )
+        .unwrap();
+        let ctx = BasicContext::default();
+        let check = |fragment: &str| {
+            check_filesystem(
+                &temp,
+                Path::new("page.html"),
+                Some(fragment),
+                &ctx,
+            )
+        };
+
+        check("section").unwrap();
+        check("legacy-anchor").unwrap();
+        let err = check("not-an-anchor").unwrap_err();
+        assert!(matches!(err, Reason::File), "{:?}", err);
+    }
+
+    #[test]
+    fn markdown_links_to_html_fragments_use_the_html_file() {
+        init_logging();
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::write(
+            temp.join("page.html"),
+            r#"<h2 id="getting-started">Getting Started</h2>"#,
+        )
+        .unwrap();
+        let ctx = BasicContext::default();
+
+        check_filesystem(
+            &temp,
+            Path::new("page.md"),
+            Some("getting-started"),
+            &ctx,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn markdown_fragments_use_the_configured_slugger() {
+        init_logging();
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::write(temp.join("README.md"), "# A -- B\n").unwrap();
+        let mut ctx = BasicContext::default();
+        let check = |ctx: &BasicContext, fragment: &str| {
+            check_filesystem(
+                &temp,
+                Path::new("README.md"),
+                Some(fragment),
+                ctx,
+            )
+        };
+
+        ctx.options = Options::default().set_slugger(GitLabSlugger);
+        check(&ctx, "a-b").unwrap();
+        assert!(check(&ctx, "a----b").is_err());
+
+        ctx.options = Options::default().set_slugger(GitHubSlugger);
+        check(&ctx, "a----b").unwrap();
+        assert!(check(&ctx, "a-b").is_err());
+    }
+
+    #[tokio::test]
+    async fn the_context_decides_how_serious_a_warning_is() {
+        init_logging();
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::write(temp.join("README.md"), "# Getting Started\n").unwrap();
+        let mut files = codespan::Files::new();
+        let file = files.add("index.md", "");
+        let link = || {
+            crate::Link::new(
+                "README.md#Getting-Started",
+                Default::default(),
+                file,
+                "index.md".into(),
+            )
+        };
+
+        let ctx = BasicContext::default();
+        let got = crate::validate(&temp, vec![link()], &ctx).await;
+        assert_eq!(got.invalid.len(), 1);
+        assert!(matches!(got.invalid[0].reason, Reason::Warning(_)));
+
+        let ctx = BasicContext::default().set_severity(
+            WarningKind::CaseInsensitiveFragment,
+            Severity::Warning,
+        );
+        let got = crate::validate(&temp, vec![link()], &ctx).await;
+        assert_eq!(got.warnings.len(), 1);
+        assert_eq!(
+            got.warnings[0].warning,
+            Warning::CaseInsensitiveFragment {
+                anchor: String::from("getting-started")
+            }
+        );
+
+        let ctx = BasicContext::default().set_severity(
+            WarningKind::CaseInsensitiveFragment,
+            Severity::Allow,
+        );
+        let got = crate::validate(&temp, vec![link()], &ctx).await;
+        assert_eq!(got.valid.len(), 1);
+    }
+
+    #[test]
+    fn fragments_with_the_wrong_case_are_broken_by_default() {
+        init_logging();
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::write(temp.join("README.md"), "# Getting Started\n").unwrap();
+        let readme = Path::new("README.md");
+
+        let got = check_filesystem(
+            &temp,
+            readme,
+            Some("GETTING-STARTED"),
+            &BasicContext::default(),
+        );
+
+        assert!(matches!(
+            got,
+            Err(Reason::Warning(Warning::CaseInsensitiveFragment { .. }))
+        ));
+
+        let ctx = BasicContext::default().set_severity(
+            WarningKind::CaseInsensitiveFragment,
+            Severity::Warning,
+        );
+        check_filesystem(&temp, readme, Some("GETTING-STARTED"), &ctx).unwrap();
+    }
+
+    #[tokio::test]
+    async fn suggest_similar_files_and_anchors() {
+        init_logging();
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        std::fs::write(temp.join("README.md"), "# Getting Started\n").unwrap();
+        let mut files = codespan::Files::new();
+        let file = files.add("README.md", "");
+        let links = ["README.md#getting-startd", "REDME.md", "#getting-startd"]
+            .iter()
+            .map(|href| {
+                crate::Link::new(
+                    *href,
+                    Default::default(),
+                    file,
+                    "README.md".into(),
+                )
+            });
+        let ctx = BasicContext::default();
+
+        let mut got = crate::validate(&temp, links, &ctx).await.invalid;
+        got.sort_by(|left, right| left.link.href.cmp(&right.link.href));
+
+        let suggestions: Vec<_> = got
+            .iter()
+            .map(|invalid| {
+                (invalid.link.href.as_str(), &invalid.suggestions[..])
+            })
+            .collect();
+        assert_eq!(
+            suggestions,
+            vec![
+                ("#getting-startd", &[String::from("#getting-started")][..]),
+                (
+                    "README.md#getting-startd",
+                    &[String::from("README.md#getting-started")][..]
+                ),
+                ("REDME.md", &[String::from("README.md")][..]),
+            ]
+        );
+    }
+
     #[test]
     fn join_paths() {
         init_logging();
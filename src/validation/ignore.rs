@@ -0,0 +1,331 @@
+use crate::{Link, LinkKind};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    path::Path,
+};
+use url::Url;
+
+/// A set of [`IgnoreRule`]s and host allow/deny lists, used to decide which
+/// links shouldn't be checked.
+///
+/// # Examples
+///
+/// ```rust
+/// use linkcheck::validation::{IgnoreRule, IgnoreRules};
+/// use regex::Regex;
+///
+/// let rules = IgnoreRules::new()
+///     .add_rule(
+///         IgnoreRule::href(Regex::new("^https://localhost").unwrap())
+///             .with_reason("only works on a developer's machine"),
+///     )
+///     .add_rule(IgnoreRule::file_glob("CHANGELOG.md").unwrap())
+///     .deny_host("twitter.com");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+    allowed_hosts: Option<HashSet<String>>,
+}
+
+impl IgnoreRules {
+    /// Create an empty set of [`IgnoreRules`] which doesn't ignore anything.
+    pub fn new() -> Self { IgnoreRules::default() }
+
+    /// Add an [`IgnoreRule`].
+    pub fn add_rule(mut self, rule: IgnoreRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Ignore every link to a host (and its subdomains).
+    pub fn deny_host<S: Into<String>>(self, host: S) -> Self {
+        self.add_rule(IgnoreRule::host(host))
+    }
+
+    /// Only check web links to these hosts (and their subdomains), ignoring
+    /// everything else.
+    pub fn allow_host<S: Into<String>>(mut self, host: S) -> Self {
+        self.allowed_hosts
+            .get_or_insert_with(HashSet::new)
+            .insert(host.into().to_lowercase());
+        self
+    }
+
+    /// The [`IgnoreRule`]s, in the order they are checked.
+    pub fn rules(&self) -> &[IgnoreRule] { &self.rules }
+
+    /// Does nothing get ignored?
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.allowed_hosts.is_none()
+    }
+
+    /// Work out why a [`Link`] should be ignored, if it should be.
+    ///
+    /// The `document` is the path of the file containing the link, relative
+    /// to wherever file globs should be matched from (e.g. the
+    /// [`crate::validation::Options::root_directory()`]). No normalising is
+    /// done here, so a path like `./vendor/README.md` won't match
+    /// `vendor/**`.
+    pub fn check(&self, link: &Link, document: &Path) -> Option<String> {
+        if let Some(rule) =
+            self.rules.iter().find(|rule| rule.is_match(link, document))
+        {
+            return Some(rule.reason());
+        }
+
+        let allowed_hosts = self.allowed_hosts.as_ref()?;
+        let url = Url::parse(&link.href).ok()?;
+        let host = url.host_str()?;
+
+        if allowed_hosts
+            .iter()
+            .any(|allowed| is_same_site(host, allowed))
+        {
+            None
+        } else {
+            Some(format!("\"{}\" isn't one of the allowed hosts", host))
+        }
+    }
+}
+
+/// A single reason for ignoring a [`Link`].
+#[derive(Debug, Clone)]
+pub struct IgnoreRule {
+    pattern: Pattern,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Href(Regex),
+    HrefGlob(GlobMatcher),
+    Host(String),
+    File(GlobMatcher),
+    Kind(LinkKind),
+}
+
+impl IgnoreRule {
+    /// Ignore links whose `href` matches a [`Regex`].
+    pub fn href(pattern: Regex) -> Self {
+        IgnoreRule::new(Pattern::Href(pattern))
+    }
+
+    /// Ignore links whose `href` matches a glob (e.g.
+    /// `https://example.com/private/*`).
+    ///
+    /// A `*` never matches a `/`, so use `**` to match any depth.
+    pub fn href_glob(glob: &str) -> Result<Self, globset::Error> {
+        Ok(IgnoreRule::new(Pattern::HrefGlob(matcher(glob)?)))
+    }
+
+    /// Ignore web links to a host (and its subdomains).
+    pub fn host<S: Into<String>>(host: S) -> Self {
+        IgnoreRule::new(Pattern::Host(host.into().to_lowercase()))
+    }
+
+    /// Ignore every link in documents matching a glob (e.g. `vendor/**`).
+    ///
+    /// A `*` never matches a `/`, so `docs/*.md` only matches documents
+    /// directly inside `docs/`.
+    pub fn file_glob(glob: &str) -> Result<Self, globset::Error> {
+        Ok(IgnoreRule::new(Pattern::File(matcher(glob)?)))
+    }
+
+    /// Ignore every link written a particular way (e.g. all images).
+    pub fn kind(kind: LinkKind) -> Self { IgnoreRule::new(Pattern::Kind(kind)) }
+
+    fn new(pattern: Pattern) -> Self {
+        IgnoreRule {
+            pattern,
+            reason: None,
+        }
+    }
+
+    /// Explain why links matching this rule are ignored.
+    pub fn with_reason<S: Into<String>>(mut self, reason: S) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// Why links matching this rule are ignored, falling back to a
+    /// description of the rule itself.
+    pub fn reason(&self) -> String {
+        match self.reason {
+            Some(ref reason) => reason.clone(),
+            None => self.to_string(),
+        }
+    }
+
+    /// Does this rule apply to a [`Link`] in a particular document?
+    pub fn is_match(&self, link: &Link, document: &Path) -> bool {
+        match self.pattern {
+            Pattern::Href(ref regex) => regex.is_match(&link.href),
+            Pattern::HrefGlob(ref glob) => glob.is_match(&link.href),
+            Pattern::Host(ref denied) => Url::parse(&link.href)
+                .ok()
+                .and_then(|url| url.host_str().map(|h| is_same_site(h, denied)))
+                .unwrap_or(false),
+            Pattern::File(ref glob) => glob.is_match(document),
+            Pattern::Kind(kind) => link.kind == Some(kind),
+        }
+    }
+}
+
+impl Display for IgnoreRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.pattern {
+            Pattern::Href(ref regex) => {
+                write!(f, "the link matches \"{}\"", regex)
+            },
+            Pattern::HrefGlob(ref glob) => {
+                write!(f, "the link matches \"{}\"", glob.glob())
+            },
+            Pattern::Host(ref host) => {
+                write!(f, "links to \"{}\" are ignored", host)
+            },
+            Pattern::File(ref glob) => {
+                write!(f, "the document matches \"{}\"", glob.glob())
+            },
+            Pattern::Kind(kind) => write!(f, "{} links are ignored", kind),
+        }
+    }
+}
+
+/// Is `host` the same as `site`, or one of its subdomains?
+fn is_same_site(host: &str, site: &str) -> bool {
+    let host = host.to_lowercase();
+
+    host == site
+        || host
+            .strip_suffix(site)
+            .map(|prefix| prefix.ends_with('.'))
+            .unwrap_or(false)
+}
+
+fn matcher(glob: &str) -> Result<GlobMatcher, globset::Error> {
+    let glob = GlobBuilder::new(glob).literal_separator(true).build()?;
+    Ok(glob.compile_matcher())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::link,
+        validation::{Context, Options},
+        BasicContext,
+    };
+
+    #[test]
+    fn record_which_rule_matched() {
+        let rules = IgnoreRules::new()
+            .add_rule(
+                IgnoreRule::href(Regex::new("^https://localhost").unwrap())
+                    .with_reason("only works locally"),
+            )
+            .add_rule(
+                IgnoreRule::href_glob("https://example.com/private/*").unwrap(),
+            )
+            .add_rule(IgnoreRule::file_glob("vendor/**").unwrap())
+            .add_rule(IgnoreRule::kind(LinkKind::Image))
+            .deny_host("twitter.com");
+        let readme = Path::new("README.md");
+
+        let inputs = vec![
+            (
+                "https://localhost:8000/",
+                readme,
+                Some("only works locally"),
+            ),
+            (
+                "https://example.com/private/x",
+                readme,
+                Some("the link matches \"https://example.com/private/*\""),
+            ),
+            (
+                "./index.md",
+                Path::new("vendor/lib/README.md"),
+                Some("the document matches \"vendor/**\""),
+            ),
+            (
+                "https://mobile.twitter.com/x",
+                readme,
+                Some("links to \"twitter.com\" are ignored"),
+            ),
+            ("https://nottwitter.com/", readme, None),
+            ("https://example.com/public", readme, None),
+        ];
+
+        for (href, document, should_be) in inputs {
+            let got = rules.check(&link(href), document);
+            assert_eq!(got.as_deref(), should_be, "{}", href);
+        }
+
+        let mut image = link("logo.png");
+        image.kind = Some(LinkKind::Image);
+        assert_eq!(
+            rules.check(&image, readme).as_deref(),
+            Some("image links are ignored")
+        );
+    }
+
+    #[test]
+    fn only_check_allowed_hosts() {
+        let rules = IgnoreRules::new().allow_host("example.com");
+        let readme = Path::new("README.md");
+
+        assert!(rules
+            .check(&link("https://docs.example.com/"), readme)
+            .is_none());
+        assert!(rules.check(&link("./README.md"), readme).is_none());
+        assert_eq!(
+            rules
+                .check(&link("https://rust-lang.org/"), readme)
+                .unwrap(),
+            "\"rust-lang.org\" isn't one of the allowed hosts"
+        );
+    }
+
+    #[test]
+    fn a_single_star_never_crosses_directories() {
+        let rules = IgnoreRules::new()
+            .add_rule(IgnoreRule::file_glob("docs/*.md").unwrap())
+            .add_rule(
+                IgnoreRule::href_glob("https://example.com/private/*").unwrap(),
+            );
+        let readme = Path::new("README.md");
+
+        assert!(rules
+            .check(&link("./x.md"), Path::new("docs/guide.md"))
+            .is_some());
+        assert!(rules
+            .check(&link("./x.md"), Path::new("docs/deep/nested/guide.md"))
+            .is_none());
+        assert!(rules
+            .check(&link("https://example.com/private/x"), readme)
+            .is_some());
+        assert!(rules
+            .check(&link("https://example.com/private/x/y"), readme)
+            .is_none());
+    }
+
+    #[test]
+    fn match_file_globs_against_relative_documents() {
+        let rules = IgnoreRules::new()
+            .add_rule(IgnoreRule::file_glob("validation/*.rs").unwrap());
+        let mut ctx = BasicContext::default().set_ignore_rules(rules);
+        ctx.options = Options::default().with_root_directory("./src").unwrap();
+        let document = Path::new("./src/validation/../validation/ignore.rs");
+
+        let got = ctx.ignore_reason(&link("./mod.rs"), document);
+
+        assert_eq!(
+            got.as_deref(),
+            Some("the document matches \"validation/*.rs\"")
+        );
+    }
+}
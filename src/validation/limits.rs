@@ -0,0 +1,224 @@
+use crate::validation::Context;
+use reqwest::Url;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+/// Limits on how many requests we'll send to a single host.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HostLimits {
+    /// The maximum number of requests that may be in flight at a time.
+    pub concurrency: usize,
+    /// The maximum number of requests to start each second, or [`None`] for
+    /// no limit.
+    pub requests_per_second: Option<f64>,
+}
+
+impl HostLimits {
+    /// The minimum amount of time between the start of two requests.
+    fn interval(&self) -> Option<Duration> {
+        self.requests_per_second
+            .filter(|rps| rps.is_finite() && *rps > 0.0)
+            .map(|rps| Duration::from_secs_f64(1.0 / rps))
+    }
+}
+
+impl Default for HostLimits {
+    fn default() -> Self {
+        HostLimits {
+            concurrency: 8,
+            requests_per_second: Some(10.0),
+        }
+    }
+}
+
+/// Hands out permission to send requests, making sure we respect both the
+/// global [`Context::concurrency()`] and any per-host [`HostLimits`].
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    global: Arc<Semaphore>,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+impl Limiter {
+    pub(crate) fn new(concurrency: usize) -> Self {
+        Limiter {
+            global: Arc::new(Semaphore::new(permits(concurrency))),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait until we're allowed to send a request to this [`Url`].
+    ///
+    /// The request may be sent for as long as the [`Permit`] is alive.
+    pub(crate) async fn acquire<C>(&self, url: &Url, ctx: &C) -> Permit
+    where
+        C: Context + ?Sized,
+    {
+        let host = self.host(url, ctx);
+
+        // Wait for our host before taking a global permit so a busy host
+        // doesn't stop other hosts from being checked in parallel
+        let host_permit = Arc::clone(&host.permits)
+            .acquire_owned()
+            .await
+            .expect("The semaphore is never closed");
+
+        if let Some(start) = host.reserve_slot() {
+            tokio::time::sleep_until(start).await;
+        }
+
+        let global_permit = Arc::clone(&self.global)
+            .acquire_owned()
+            .await
+            .expect("The semaphore is never closed");
+
+        Permit {
+            _host: host_permit,
+            _global: global_permit,
+        }
+    }
+
+    fn host<C>(&self, url: &Url, ctx: &C) -> Arc<Host>
+    where
+        C: Context + ?Sized,
+    {
+        let name = url.host_str().unwrap_or_default();
+        let mut hosts = self.hosts.lock().expect("Mutex was poisoned");
+
+        if let Some(host) = hosts.get(name) {
+            return Arc::clone(host);
+        }
+
+        let limits = ctx.host_limits(name);
+        log::trace!("Using {:?} for \"{}\"", limits, name);
+        let host = Arc::new(Host::new(&limits));
+        hosts.insert(name.to_string(), Arc::clone(&host));
+
+        host
+    }
+}
+
+/// Permission to send a single request.
+#[derive(Debug)]
+pub(crate) struct Permit {
+    _host: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+#[derive(Debug)]
+struct Host {
+    permits: Arc<Semaphore>,
+    interval: Option<Duration>,
+    next_request: Mutex<Instant>,
+}
+
+impl Host {
+    fn new(limits: &HostLimits) -> Self {
+        Host {
+            permits: Arc::new(Semaphore::new(permits(limits.concurrency))),
+            interval: limits.interval(),
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Reserve the next time slot a request can be sent in, if we are being
+    /// rate limited.
+    fn reserve_slot(&self) -> Option<Instant> {
+        let interval = self.interval?;
+        let mut next_request =
+            self.next_request.lock().expect("Mutex was poisoned");
+
+        let start = Instant::now().max(*next_request);
+        *next_request = start + interval;
+
+        Some(start)
+    }
+}
+
+fn permits(concurrency: usize) -> usize {
+    concurrency.clamp(1, Semaphore::MAX_PERMITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::{BasicContext, Options};
+    use futures::FutureExt;
+    use reqwest::Client;
+
+    #[derive(Debug, Default)]
+    struct LimitedContext {
+        inner: BasicContext,
+    }
+
+    impl Context for LimitedContext {
+        fn client(&self) -> &Client { self.inner.client() }
+
+        fn filesystem_options(&self) -> &Options {
+            self.inner.filesystem_options()
+        }
+
+        fn host_limits(&self, host: &str) -> HostLimits {
+            match host {
+                "slow.example.com" => HostLimits {
+                    concurrency: 1,
+                    requests_per_second: Some(2.0),
+                },
+                _ => HostLimits {
+                    concurrency: 1,
+                    requests_per_second: None,
+                },
+            }
+        }
+    }
+
+    fn url(s: &str) -> Url { s.parse().unwrap() }
+
+    #[tokio::test]
+    async fn busy_hosts_dont_block_other_hosts() {
+        let ctx = LimitedContext::default();
+        let limiter = Limiter::new(64);
+        let first = url("https://github.com/a");
+        let second = url("https://github.com/b");
+        let other = url("https://docs.rs/");
+
+        let _permit = limiter.acquire(&first, &ctx).await;
+
+        assert!(limiter.acquire(&second, &ctx).now_or_never().is_none());
+        assert!(limiter.acquire(&other, &ctx).now_or_never().is_some());
+    }
+
+    #[tokio::test]
+    async fn the_global_limit_applies_to_all_hosts() {
+        let ctx = LimitedContext::default();
+        let limiter = Limiter::new(1);
+
+        let _permit = limiter.acquire(&url("https://github.com/"), &ctx).await;
+
+        assert!(limiter
+            .acquire(&url("https://docs.rs/"), &ctx)
+            .now_or_never()
+            .is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_are_rate_limited() {
+        let ctx = LimitedContext::default();
+        let limiter = Limiter::new(64);
+        let url = url("https://slow.example.com/");
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            let _permit = limiter.acquire(&url, &ctx).await;
+        }
+
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+}
@@ -1,18 +1,48 @@
 //! Code for validating the various types of [`Link`].
 
+mod anchors;
 mod cache;
 mod context;
 mod filesystem;
+mod ignore;
+mod limits;
+mod redirect;
+mod retry;
+mod slug;
+mod suggest;
+mod warning;
 mod web;
+mod web_error;
 
-pub use cache::{Cache, CacheEntry};
+pub use cache::{Cache, CacheEntry, FailureKind};
 pub use context::{BasicContext, Context};
 pub use filesystem::{check_filesystem, resolve_link, Options};
-pub use web::{check_web, head};
+pub use ignore::{IgnoreRule, IgnoreRules};
+pub use limits::HostLimits;
+pub use redirect::Redirect;
+pub use retry::RetryPolicy;
+pub use slug::{
+    GitHubSlugger, GitLabSlugger, MdbookSlugger, RustdocSlugger, Slugger,
+};
+pub use warning::{Severity, Warning, WarningKind};
+pub use web::{check_web, get, head, MethodPolicy, WebCheck};
+pub use web_error::{WebError, WebErrorKind};
+
+pub(crate) use filesystem::file_anchors;
+pub(crate) use suggest::with_fragment;
 
 use crate::{Category, Link};
-use futures::{Future, StreamExt};
-use std::path::{Path, PathBuf};
+use filesystem::check_filesystem_with_warnings;
+use futures::{stream::FuturesUnordered, Future, StreamExt};
+use http::StatusCode;
+use reqwest::Url;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use suggest::suggestions;
+use warning::is_insecure;
+pub(crate) use web::{check_web_in_session, Session};
 
 /// Possible reasons for a bad link.
 #[derive(Debug, thiserror::Error)]
@@ -24,15 +54,33 @@ pub enum Reason {
     /// The OS returned an error (e.g. file not found).
     #[error("An OS-level error occurred")]
     Io(#[from] std::io::Error),
+    /// None of the places the linked file could be found actually exist.
+    #[error("The file doesn't exist")]
+    NotFound {
+        /// Every path that was checked, in the order they were tried.
+        tried: Vec<PathBuf>,
+    },
     /// The file doesn't contain the fragment.
     #[error("The file exists, but not the anchor")]
     File,
     /// The HTTP client returned an error.
-    #[error("The web client encountered an error")]
-    Web(#[from] reqwest::Error),
+    #[error(transparent)]
+    Web(#[from] WebError),
     /// The DOM doesn't contain the fragment.
     #[error("The web page exists, but not the anchor")]
     Dom,
+    /// A [`Warning`] which the [`Context::severity()`] says should be treated
+    /// as an error.
+    #[error(transparent)]
+    Warning(Warning),
+    /// The [`Cache`] says this link was broken the last time it was checked.
+    #[error("The link was broken when it was last checked ({failure})")]
+    Cached {
+        /// Why the link was broken.
+        failure: FailureKind,
+        /// When the link was last checked.
+        checked_at: SystemTime,
+    },
 }
 
 impl Reason {
@@ -40,6 +88,7 @@ impl Reason {
     pub fn file_not_found(&self) -> bool {
         match self {
             Reason::Io(e) => e.kind() == std::io::ErrorKind::NotFound,
+            Reason::NotFound { .. } => true,
             _ => false,
         }
     }
@@ -51,9 +100,43 @@ impl Reason {
             _ => false,
         }
     }
+
+    /// Get the [`WebError`], if this was a failed web request.
+    pub fn web_error(&self) -> Option<&WebError> {
+        match self {
+            Reason::Web(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// What sort of [`WebError`] was encountered?
+    pub fn web_error_kind(&self) -> Option<WebErrorKind> {
+        self.web_error().map(WebError::kind)
+    }
+
+    /// The status code sent by the server, if this was a failed web request.
+    pub fn status(&self) -> Option<StatusCode> {
+        self.web_error().and_then(WebError::status)
+    }
+
+    /// The URL of the final request (after following redirects), if this was
+    /// a failed web request.
+    pub fn final_url(&self) -> Option<&Url> {
+        self.web_error().and_then(WebError::url)
+    }
+}
+
+impl From<reqwest::Error> for Reason {
+    fn from(error: reqwest::Error) -> Self {
+        Reason::Web(WebError::from(error))
+    }
 }
 
 /// Validate several [`Link`]s relative to a particular directory.
+///
+/// Web requests are throttled using [`Context::concurrency()`] and
+/// [`Context::host_limits()`], so links to different hosts can be checked in
+/// parallel without overwhelming any single server.
 pub fn validate<'a, L, C>(
     current_directory: &'a Path,
     links: L,
@@ -64,10 +147,43 @@ where
     L::IntoIter: 'a,
     C: Context + ?Sized,
 {
-    futures::stream::iter(links)
-        .map(move |link| validate_one(link, current_directory, ctx))
-        .buffer_unordered(ctx.concurrency())
-        .collect()
+    let links = links
+        .into_iter()
+        .map(move |link| (current_directory.to_path_buf(), link));
+
+    validate_in_directories(links, ctx)
+}
+
+/// Validate [`Link`]s from several directories at once, resolving each
+/// [`Link`] relative to the directory it is paired with.
+///
+/// Prefer this to calling [`validate()`] once per directory, because every
+/// link shares the same [`Context::concurrency()`] and
+/// [`Context::host_limits()`], and a URL linked to from several directories
+/// is only requested once.
+pub fn validate_in_directories<'a, L, C>(
+    links: L,
+    ctx: &'a C,
+) -> impl Future<Output = Outcomes> + 'a
+where
+    L: IntoIterator<Item = (PathBuf, Link)>,
+    L::IntoIter: 'a,
+    C: Context + ?Sized,
+{
+    let links = links.into_iter();
+
+    async move {
+        let session = Session::new(ctx);
+        let session = &session;
+
+        links
+            .map(|(directory, link)| async move {
+                validate_one(link, &directory, ctx, session).await
+            })
+            .collect::<FuturesUnordered<_>>()
+            .collect()
+            .await
+    }
 }
 
 /// Try to validate a single link, deferring to the appropriate validator based
@@ -76,51 +192,90 @@ async fn validate_one<C>(
     link: Link,
     current_directory: &Path,
     ctx: &C,
+    session: &Session,
 ) -> Outcome
 where
     C: Context + ?Sized,
 {
-    if ctx.should_ignore(&link) {
-        log::debug!("Ignoring \"{}\"", link.href);
-        return Outcome::Ignored(link);
+    if link.suppressed {
+        log::debug!("Ignoring \"{}\" because it was suppressed", link.href);
+        return Outcome::Ignored(IgnoredLink {
+            link,
+            reason: String::from("suppressed inline"),
+        });
     }
 
-    match link.category() {
-        Some(Category::FileSystem { path, fragment }) => Outcome::from_result(
-            link,
-            check_filesystem(
+    let document = current_directory.join(&link.file_name);
+
+    if let Some(reason) = ctx.ignore_reason(&link, &document) {
+        log::debug!("Ignoring \"{}\" because {}", link.href, reason);
+        return Outcome::Ignored(IgnoredLink { link, reason });
+    }
+
+    let mut web_check = None;
+    let result = match link.category() {
+        Some(Category::FileSystem { path, fragment }) => {
+            check_filesystem_with_warnings(
                 current_directory,
                 &path,
                 fragment.as_deref(),
                 ctx,
-            ),
-        ),
+            )
+        },
         Some(Category::CurrentFile { fragment }) => {
-            let res = check_filesystem(
+            check_filesystem_with_warnings(
                 current_directory,
                 &PathBuf::from(&link.file_name),
                 Some(&fragment),
                 ctx,
-            );
-            Outcome::from_result(link, res)
-        }
+            )
+        },
         Some(Category::Url(url)) => {
-            Outcome::from_result(link, check_web(&url, ctx).await)
-        }
-        Some(Category::MailTo(_)) => Outcome::Ignored(link),
-        None => Outcome::UnknownCategory(link),
+            match check_web_in_session(&url, ctx, session).await {
+                Ok((mut warnings, check)) => {
+                    if is_insecure(&url) {
+                        warnings.insert(0, Warning::InsecureLink);
+                    }
+                    web_check = Some(check);
+                    Ok(warnings)
+                },
+                Err(reason) => Err(reason),
+            }
+        },
+        Some(Category::MailTo(_)) => {
+            return Outcome::Ignored(IgnoredLink {
+                link,
+                reason: String::from("mailto: links aren't checked"),
+            })
+        },
+        None => return Outcome::UnknownCategory(link),
+    };
+
+    let mut outcome = Outcome::new(link, result, web_check, ctx);
+
+    if let Outcome::Invalid(ref mut invalid) = outcome {
+        invalid.suggestions =
+            suggestions(invalid, current_directory, ctx, session);
     }
+
+    outcome
 }
 
 /// The result of validating a batch of [`Link`]s.
 #[derive(Debug, Default)]
+#[non_exhaustive]
 pub struct Outcomes {
     /// Valid links.
-    pub valid: Vec<Link>,
+    pub valid: Vec<ValidLink>,
     /// Links which are broken.
     pub invalid: Vec<InvalidLink>,
+    /// Links which work, but have a [`Warning`] attached.
+    ///
+    /// A link with several [`Warning`]s will appear here once for each
+    /// [`Warning`].
+    pub warnings: Vec<LinkWarning>,
     /// Items that were explicitly ignored by the [`Context`].
-    pub ignored: Vec<Link>,
+    pub ignored: Vec<IgnoredLink>,
     /// Links which we weren't able to identify a suitable validator for.
     pub unknown_category: Vec<Link>,
 }
@@ -135,6 +290,7 @@ impl Outcomes {
     pub fn merge(&mut self, other: Outcomes) {
         self.valid.extend(other.valid);
         self.invalid.extend(other.invalid);
+        self.warnings.extend(other.warnings);
         self.ignored.extend(other.ignored);
         self.unknown_category.extend(other.unknown_category);
     }
@@ -146,6 +302,7 @@ impl Extend<Outcome> for Outcomes {
             match outcome {
                 Outcome::Valid(v) => self.valid.push(v),
                 Outcome::Invalid(i) => self.invalid.push(i),
+                Outcome::Warnings(w) => self.warnings.extend(w),
                 Outcome::Ignored(i) => self.ignored.push(i),
                 Outcome::UnknownCategory(u) => self.unknown_category.push(u),
             }
@@ -161,34 +318,194 @@ impl Extend<Outcomes> for Outcomes {
     }
 }
 
+/// A [`Link`] which works.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ValidLink {
+    /// The link.
+    pub link: Link,
+    /// How the link was checked, if it was checked on the web.
+    pub web_check: Option<WebCheck>,
+}
+
+impl ValidLink {
+    /// Create a new [`ValidLink`] which wasn't checked on the web.
+    pub fn new(link: Link) -> Self {
+        ValidLink {
+            link,
+            web_check: None,
+        }
+    }
+}
+
 /// A [`Link`] and the [`Reason`] why it is invalid.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct InvalidLink {
     /// The invalid link.
     pub link: Link,
     /// Why is this link invalid?
     pub reason: Reason,
+    /// Similar `href`s which would have worked (e.g. a file in the same
+    /// directory with a similar name, or the closest anchor in the linked
+    /// document), best first.
+    pub suggestions: Vec<String>,
+}
+
+impl InvalidLink {
+    /// Create a new [`InvalidLink`] without any suggestions.
+    pub fn new(link: Link, reason: Reason) -> Self {
+        InvalidLink {
+            link,
+            reason,
+            suggestions: Vec::new(),
+        }
+    }
+}
+
+/// A [`Link`] which wasn't checked, and why.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct IgnoredLink {
+    /// The link.
+    pub link: Link,
+    /// Why it was ignored (e.g. the reason attached to the [`IgnoreRule`]
+    /// that matched).
+    pub reason: String,
+}
+
+impl IgnoredLink {
+    /// Create a new [`IgnoredLink`].
+    pub fn new<S: Into<String>>(link: Link, reason: S) -> Self {
+        IgnoredLink {
+            link,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A [`Link`] which works, and the [`Warning`] attached to it.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct LinkWarning {
+    /// The link.
+    pub link: Link,
+    /// What should be looked at?
+    pub warning: Warning,
+    /// How the link was checked, if it was checked on the web.
+    pub web_check: Option<WebCheck>,
+}
+
+impl LinkWarning {
+    /// Create a new [`LinkWarning`] for a link which wasn't checked on the
+    /// web.
+    pub fn new(link: Link, warning: Warning) -> Self {
+        LinkWarning {
+            link,
+            warning,
+            web_check: None,
+        }
+    }
 }
 
 #[derive(Debug)]
 enum Outcome {
-    Valid(Link),
+    Valid(ValidLink),
     Invalid(InvalidLink),
-    Ignored(Link),
+    Warnings(Vec<LinkWarning>),
+    Ignored(IgnoredLink),
     UnknownCategory(Link),
 }
 
 impl Outcome {
-    fn from_result<T, E>(link: Link, result: Result<T, E>) -> Self
+    /// Work out what happened to a [`Link`], using the [`Context`] to decide
+    /// how seriously to take any [`Warning`]s.
+    fn new<C>(
+        link: Link,
+        result: Result<Vec<Warning>, Reason>,
+        web_check: Option<WebCheck>,
+        ctx: &C,
+    ) -> Self
     where
-        E: Into<Reason>,
+        C: Context + ?Sized,
     {
-        match result {
-            Ok(_) => Outcome::Valid(link),
-            Err(e) => Outcome::Invalid(InvalidLink {
-                link,
-                reason: e.into(),
-            }),
+        let warnings = match result {
+            Ok(warnings) => warnings,
+            Err(reason) => {
+                return Outcome::Invalid(InvalidLink::new(link, reason))
+            },
+        };
+
+        let mut reported = Vec::new();
+
+        for warning in warnings {
+            match ctx.severity(&link, &warning) {
+                Severity::Allow => {},
+                Severity::Warning => reported.push(warning),
+                Severity::Error => {
+                    return Outcome::Invalid(InvalidLink::new(
+                        link,
+                        Reason::Warning(warning),
+                    ))
+                },
+            }
         }
+
+        if reported.is_empty() {
+            Outcome::Valid(ValidLink { link, web_check })
+        } else {
+            let warnings = reported
+                .into_iter()
+                .map(|warning| LinkWarning {
+                    link: link.clone(),
+                    warning,
+                    web_check,
+                })
+                .collect();
+            Outcome::Warnings(warnings)
+        }
+    }
+}
+
+/// Turn any [`Warning`]s the [`Context::severity()`] says are errors into a
+/// [`Reason`], for checks like [`check_filesystem()`] which only say whether
+/// a link is broken.
+pub(crate) fn escalate_warnings<C>(
+    href: &str,
+    warnings: Vec<Warning>,
+    ctx: &C,
+) -> Result<(), Reason>
+where
+    C: Context + ?Sized,
+{
+    // we don't know which document the link came from, so it gets a
+    // placeholder file
+    let file = codespan::Files::new().add("", "");
+    let link = Link::new(href, Default::default(), file, Default::default());
+
+    match warnings
+        .into_iter()
+        .find(|warning| ctx.severity(&link, warning) == Severity::Error)
+    {
+        Some(warning) => Err(Reason::Warning(warning)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::link, BasicContext};
+
+    #[tokio::test]
+    async fn suppressed_links_are_never_checked() {
+        let mut link = link("./missing.md");
+        link.suppressed = true;
+        let ctx = BasicContext::default();
+
+        let got = validate(Path::new("."), vec![link], &ctx).await;
+
+        assert_eq!(got.ignored.len(), 1);
+        assert_eq!(got.ignored[0].reason, "suppressed inline");
     }
 }
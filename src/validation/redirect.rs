@@ -0,0 +1,85 @@
+use http::{header::LOCATION, StatusCode};
+use reqwest::{Response, Url};
+
+/// The maximum number of redirects we'll follow before giving up.
+pub(crate) const MAX_REDIRECTS: usize = 10;
+
+/// A single hop in a chain of redirects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-1", derive(serde::Serialize, serde::Deserialize))]
+pub struct Redirect {
+    /// The URL which was requested.
+    pub from: Url,
+    /// Where the server sent us instead.
+    pub to: Url,
+    /// The status code used for the redirect (e.g. `301`).
+    pub status: u16,
+}
+
+impl Redirect {
+    /// Is this a permanent redirect (`301 Moved Permanently` or `308
+    /// Permanent Redirect`)?
+    ///
+    /// Links which are permanently redirected should be updated to point at
+    /// the new location.
+    pub fn is_permanent(&self) -> bool {
+        self.status == StatusCode::MOVED_PERMANENTLY.as_u16()
+            || self.status == StatusCode::PERMANENT_REDIRECT.as_u16()
+    }
+}
+
+/// If a chain of redirects starts with permanent redirects, where do they
+/// lead?
+///
+/// We stop at the first temporary redirect, because wherever it points (e.g.
+/// a login page or a translation) isn't where the link should go.
+pub(crate) fn permanent_destination(redirects: &[Redirect]) -> Option<&Url> {
+    redirects
+        .iter()
+        .take_while(|redirect| redirect.is_permanent())
+        .last()
+        .map(|redirect| &redirect.to)
+}
+
+/// If this [`Response`] is a redirect, where does it point?
+pub(crate) fn location(response: &Response) -> Option<Url> {
+    let status = response.status();
+    if !status.is_redirection() || status == StatusCode::NOT_MODIFIED {
+        return None;
+    }
+
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    response.url().join(location).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(from: &str, to: &str, status: u16) -> Redirect {
+        Redirect {
+            from: from.parse().unwrap(),
+            to: to.parse().unwrap(),
+            status,
+        }
+    }
+
+    #[test]
+    fn only_permanent_chains_have_a_destination() {
+        let temporary = vec![
+            redirect("http://a.com/", "http://b.com/", 302),
+            redirect("http://b.com/", "https://b.com/", 301),
+        ];
+        let permanent = vec![
+            redirect("http://a.com/", "https://a.com/", 301),
+            redirect("https://a.com/", "https://www.a.com/", 308),
+            redirect("https://www.a.com/", "https://www.a.com/en/", 302),
+        ];
+
+        assert_eq!(permanent_destination(&temporary), None);
+        assert_eq!(
+            permanent_destination(&permanent),
+            Some(&"https://www.a.com/".parse().unwrap())
+        );
+    }
+}
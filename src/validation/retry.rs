@@ -0,0 +1,220 @@
+use http::{HeaderMap, StatusCode};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{Duration, SystemTime},
+};
+
+/// How failed web requests should be retried.
+///
+/// Delays grow exponentially, starting at [`RetryPolicy::initial_backoff`]
+/// and doubling after each attempt (up to [`RetryPolicy::max_backoff`]). A
+/// server can ask us to wait for a specific amount of time by sending a
+/// `Retry-After` header with a `429 Too Many Requests` or `503 Service
+/// Unavailable` response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request will be sent, including the
+    /// first attempt. Set this to `1` to disable retries.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry.
+    pub initial_backoff: Duration,
+    /// The longest we'll wait between attempts, not counting any delay
+    /// requested by the server.
+    pub max_backoff: Duration,
+    /// Randomly shorten each delay by up to this fraction (between `0.0` and
+    /// `1.0`) so concurrent requests don't all retry at the same time.
+    pub jitter: f64,
+    /// The longest `Retry-After` delay we're willing to honour. If a server
+    /// asks us to wait any longer we'll give up instead.
+    pub max_retry_after: Duration,
+    /// Responses with these status codes will be retried.
+    pub retryable_statuses: Vec<StatusCode>,
+    /// Should timeouts and connection errors be retried?
+    pub retry_network_errors: bool,
+}
+
+impl RetryPolicy {
+    /// A [`RetryPolicy`] which never retries.
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Is this error worth retrying?
+    pub fn should_retry(&self, error: &reqwest::Error) -> bool {
+        match error.status() {
+            Some(status) => self.retryable_statuses.contains(&status),
+            None => {
+                self.retry_network_errors
+                    && (error.is_timeout() || error.is_connect())
+            },
+        }
+    }
+
+    /// How long to wait after a particular attempt (starting at `1`) before
+    /// trying again, ignoring jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempt.saturating_sub(1));
+
+        self.initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+
+    /// Work out how long to wait before the next attempt, or [`None`] if we
+    /// should give up.
+    pub(crate) fn delay(
+        &self,
+        attempt: u32,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        match retry_after {
+            Some(requested) if requested > self.max_retry_after => None,
+            Some(requested) => Some(requested),
+            None => Some(self.with_jitter(self.backoff(attempt))),
+        }
+    }
+
+    fn with_jitter(&self, delay: Duration) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+
+        // we don't need anything fancy, so avoid pulling in a random number
+        // generator by using the randomly seeded std hasher
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(delay.as_nanos());
+        let random = hasher.finish() as f64 / u64::MAX as f64;
+
+        delay.mul_f64(1.0 - jitter * random)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            jitter: 0.5,
+            max_retry_after: Duration::from_secs(60),
+            retryable_statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_network_errors: true,
+        }
+    }
+}
+
+/// Read the `Retry-After` header from a `429 Too Many Requests` or `503
+/// Service Unavailable` response.
+///
+/// The header may contain either a number of seconds or a HTTP date.
+pub(crate) fn retry_after(
+    status: StatusCode,
+    headers: &HeaderMap,
+) -> Option<Duration> {
+    if status != StatusCode::TOO_MANY_REQUESTS
+        && status != StatusCode::SERVICE_UNAVAILABLE
+    {
+        return None;
+    }
+
+    let value = headers.get(http::header::RETRY_AFTER)?.to_str().ok()?;
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::from_secs(0)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    #[test]
+    fn backoff_doubles_until_the_limit() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..RetryPolicy::default()
+        };
+
+        let got: Vec<_> =
+            (1..=5).map(|attempt| policy.backoff(attempt)).collect();
+
+        assert_eq!(
+            got,
+            vec![1, 2, 4, 5, 5]
+                .into_iter()
+                .map(Duration::from_secs)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(policy.backoff(100), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn jitter_only_ever_shortens_the_delay() {
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..RetryPolicy::default()
+        };
+
+        for attempt in 1..10 {
+            let backoff = policy.backoff(attempt);
+            let got = policy.delay(attempt, None).unwrap();
+
+            assert!(got <= backoff);
+            assert!(got >= backoff / 2);
+        }
+    }
+
+    #[test]
+    fn honour_retry_after_up_to_a_limit() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(30))),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(3600))), None);
+    }
+
+    #[test]
+    fn parse_retry_after_headers() {
+        let mut headers = HeaderMap::new();
+        headers
+            .insert(http::header::RETRY_AFTER, HeaderValue::from_static("7"));
+
+        assert_eq!(
+            retry_after(StatusCode::TOO_MANY_REQUESTS, &headers),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(retry_after(StatusCode::BAD_GATEWAY, &headers), None);
+
+        headers.insert(
+            http::header::RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(
+            retry_after(StatusCode::SERVICE_UNAVAILABLE, &headers),
+            Some(Duration::from_secs(0))
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::{collections::HashMap, fmt::Debug};
+
+/// Something which can turn a heading's text into the anchor ID a particular
+/// renderer would generate for it.
+///
+/// Different tools (GitHub, GitLab, `mdbook`, `rustdoc`, ...) all have their
+/// own rules for generating anchors, so you'll want to use the [`Slugger`]
+/// matching wherever your documents are published.
+///
+/// Duplicate headings are handled for you by appending `-1`, `-2`, etc. to the
+/// second, third, and later occurrences of a slug. This is the same scheme used
+/// by all of the built-in renderers.
+pub trait Slugger: Debug {
+    /// Generate the slug for a heading containing the provided text.
+    fn slugify(&self, heading: &str) -> String;
+}
+
+/// Generates anchors the same way `mdbook` does.
+///
+/// This is the default [`Slugger`].
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct MdbookSlugger;
+
+impl Slugger for MdbookSlugger {
+    fn slugify(&self, heading: &str) -> String {
+        mdbook::utils::normalize_id(heading.trim())
+    }
+}
+
+/// Generates anchors the same way GitHub does when rendering markdown.
+///
+/// Letters are lowercased, spaces become dashes, and all punctuation except
+/// `-` and `_` is removed. Unicode letters and numbers are kept as-is.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GitHubSlugger;
+
+impl Slugger for GitHubSlugger {
+    fn slugify(&self, heading: &str) -> String {
+        heading
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                '-' | '_' => Some(c),
+                c if c.is_alphanumeric() => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Generates anchors the same way GitLab does when rendering markdown.
+///
+/// This is similar to the [`GitHubSlugger`], except runs of dashes are
+/// collapsed into a single `-`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GitLabSlugger;
+
+impl Slugger for GitLabSlugger {
+    fn slugify(&self, heading: &str) -> String {
+        let mut slug = String::with_capacity(heading.len());
+
+        for c in heading.trim().to_lowercase().chars() {
+            let c = match c {
+                ' ' | '-' => '-',
+                c if c.is_alphanumeric() || c == '_' => c,
+                _ => continue,
+            };
+
+            if !(c == '-' && slug.ends_with('-')) {
+                slug.push(c);
+            }
+        }
+
+        slug
+    }
+}
+
+/// Generates anchors the same way `rustdoc` does for headings in doc-comments.
+///
+/// ASCII letters are lowercased, ASCII whitespace becomes a dash, and anything
+/// which isn't alphanumeric, `-` or `_` is removed.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct RustdocSlugger;
+
+impl Slugger for RustdocSlugger {
+    fn slugify(&self, heading: &str) -> String {
+        heading
+            .trim()
+            .chars()
+            .filter_map(|c| {
+                if c.is_alphanumeric() || c == '-' || c == '_' {
+                    Some(c.to_ascii_lowercase())
+                } else if c.is_ascii_whitespace() {
+                    Some('-')
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Generate a slug, making sure it hasn't been seen before.
+pub(crate) fn unique_slug(
+    slugger: &dyn Slugger,
+    heading: &str,
+    seen: &mut HashMap<String, usize>,
+) -> String {
+    let slug = slugger.slugify(heading);
+    let count = seen.entry(slug.clone()).or_insert(0);
+
+    let unique = match *count {
+        0 => slug,
+        n => format!("{}-{}", slug, n),
+    };
+    *count += 1;
+
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(slugger: &dyn Slugger, inputs: &[(&str, &str)]) {
+        for (heading, should_be) in inputs {
+            let got = slugger.slugify(heading);
+            assert_eq!(got, *should_be, "{:?}", heading);
+        }
+    }
+
+    #[test]
+    fn mdbook_slugs() {
+        check(
+            &MdbookSlugger,
+            &[
+                ("Getting Started", "getting-started"),
+                ("The `Context` trait", "the-context-trait"),
+                ("snake_case and kebab-case", "snake_case-and-kebab-case"),
+                ("What's New?", "whats-new"),
+            ],
+        );
+    }
+
+    #[test]
+    fn github_slugs() {
+        check(
+            &GitHubSlugger,
+            &[
+                ("Getting Started", "getting-started"),
+                ("snake_case_name", "snake_case_name"),
+                ("A -- B", "a----b"),
+                ("Ünïcödé Heading", "ünïcödé-heading"),
+                ("What's New? (v1.0)", "whats-new-v10"),
+            ],
+        );
+    }
+
+    #[test]
+    fn gitlab_slugs() {
+        check(
+            &GitLabSlugger,
+            &[
+                ("Getting Started", "getting-started"),
+                ("snake_case_name", "snake_case_name"),
+                ("A -- B", "a-b"),
+                ("Ünïcödé Heading", "ünïcödé-heading"),
+                ("What's New? (v1.0)", "whats-new-v10"),
+            ],
+        );
+    }
+
+    #[test]
+    fn rustdoc_slugs() {
+        check(
+            &RustdocSlugger,
+            &[
+                ("Examples", "examples"),
+                ("Cargo Features", "cargo-features"),
+                ("Ünïcödé Heading", "Ünïcödé-heading"),
+                ("`foo::bar()`", "foobar"),
+            ],
+        );
+    }
+
+    #[test]
+    fn duplicate_slugs_get_a_counter() {
+        let mut seen = HashMap::new();
+
+        let got: Vec<_> = ["Example", "Example", "Other", "Example"]
+            .iter()
+            .map(|heading| unique_slug(&GitHubSlugger, heading, &mut seen))
+            .collect();
+
+        assert_eq!(got, vec!["example", "example-1", "other", "example-2"]);
+    }
+}
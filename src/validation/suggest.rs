@@ -0,0 +1,286 @@
+//! Finding the closest match for something which doesn't exist (e.g. a
+//! misspelled anchor), so we can ask "did you mean ...?".
+
+use crate::{
+    validation::{
+        file_anchors, resolve_link, web::Session, Context, FailureKind,
+        InvalidLink, Reason,
+    },
+    Category,
+};
+use std::path::{Path, PathBuf};
+
+/// The most suggestions we'll make for a single link.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Work out which `href`s the author of an [`InvalidLink`] may have meant.
+///
+/// Missing files are compared against the other files in the same directory,
+/// while missing fragments are compared against the anchors defined by the
+/// linked document. Web pages are never downloaded just to make a
+/// suggestion, so we can only help when the page's anchors are already known.
+pub(crate) fn suggestions<C>(
+    invalid: &InvalidLink,
+    current_directory: &Path,
+    ctx: &C,
+    session: &Session,
+) -> Vec<String>
+where
+    C: Context + ?Sized,
+{
+    let link = &invalid.link;
+
+    let suggestions = match (link.category(), &invalid.reason) {
+        (Some(Category::FileSystem { .. }), Reason::NotFound { tried }) => {
+            similar_files(&link.href, tried)
+        },
+        (
+            Some(Category::FileSystem {
+                path,
+                fragment: Some(fragment),
+            }),
+            Reason::File,
+        ) => similar_file_anchors(current_directory, &path, &fragment, ctx)
+            .into_iter()
+            .map(|anchor| with_fragment(&link.href, &anchor))
+            .collect(),
+        (Some(Category::CurrentFile { fragment }), Reason::File) => {
+            similar_file_anchors(
+                current_directory,
+                &PathBuf::from(&link.file_name),
+                &fragment,
+                ctx,
+            )
+            .into_iter()
+            .map(|anchor| with_fragment(&link.href, &anchor))
+            .collect()
+        },
+        (
+            Some(Category::Url(mut url)),
+            Reason::Dom
+            | Reason::Cached {
+                failure: FailureKind::MissingAnchor,
+                ..
+            },
+        ) => {
+            let fragment = url.fragment().unwrap_or_default().to_string();
+            url.set_fragment(None);
+
+            match session.known_anchors(&url, ctx) {
+                Some(anchors) => {
+                    similar(&fragment, anchors.all.iter().map(String::as_str))
+                        .into_iter()
+                        .map(|anchor| with_fragment(&link.href, anchor))
+                        .collect()
+                },
+                None => Vec::new(),
+            }
+        },
+        _ => Vec::new(),
+    };
+
+    if !suggestions.is_empty() {
+        log::debug!(
+            "Suggesting {:?} instead of \"{}\"",
+            suggestions,
+            link.href
+        );
+    }
+
+    suggestions
+}
+
+/// Look for files next to the one a link was meant to point at.
+fn similar_files(href: &str, tried: &[PathBuf]) -> Vec<String> {
+    let requested = match tried.first() {
+        Some(requested) => requested,
+        None => return Vec::new(),
+    };
+    let (directory, name) = match (
+        requested.parent(),
+        requested.file_name().and_then(|name| name.to_str()),
+    ) {
+        (Some(directory), Some(name)) => (directory, name),
+        _ => return Vec::new(),
+    };
+
+    // we can only rewrite the href if its last segment is the file name as
+    // written (i.e. it wasn't percent-encoded or resolved to a default file)
+    if file_name(href) != name {
+        return Vec::new();
+    }
+
+    let siblings: Vec<String> = match std::fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+
+    similar(name, siblings.iter().map(String::as_str))
+        .into_iter()
+        .map(|sibling| with_file_name(href, sibling))
+        .collect()
+}
+
+/// Look for anchors in the linked file which are similar to `fragment`.
+fn similar_file_anchors<C>(
+    current_directory: &Path,
+    path: &Path,
+    fragment: &str,
+    ctx: &C,
+) -> Vec<String>
+where
+    C: Context + ?Sized,
+{
+    let options = ctx.filesystem_options();
+
+    let resolved = match resolve_link(current_directory, path, options) {
+        Ok(resolved) => resolved,
+        Err(_) => return Vec::new(),
+    };
+    let source = match std::fs::read_to_string(&resolved) {
+        Ok(source) => source,
+        Err(_) => return Vec::new(),
+    };
+    let anchors = file_anchors(&resolved, &source, options);
+
+    similar(fragment, anchors.all.iter().map(String::as_str))
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Find the candidates which are similar enough to `target` to plausibly be
+/// what was meant, most similar first.
+///
+/// Ties are broken alphabetically so the result doesn't depend on the order
+/// candidates are provided in.
+pub(crate) fn similar<'a, I>(target: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let target = target.to_lowercase();
+    let max_distance = std::cmp::max(1, target.chars().count() / 3);
+
+    let mut matches: Vec<_> = candidates
+        .into_iter()
+        .map(|candidate| {
+            (edit_distance(&target, &candidate.to_lowercase()), candidate)
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort_unstable();
+    matches.dedup();
+
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Replace (or add) the fragment at the end of an `href`.
+pub(crate) fn with_fragment(href: &str, fragment: &str) -> String {
+    format!("{}#{}", without_fragment(href), fragment)
+}
+
+/// Replace the last segment of an `href`'s path, keeping any fragment.
+fn with_file_name(href: &str, name: &str) -> String {
+    let path = without_fragment(href);
+    let directory = &path[..path.len() - file_name(href).len()];
+
+    format!("{}{}{}", directory, name, &href[path.len()..])
+}
+
+/// The last segment of an `href`'s path.
+fn file_name(href: &str) -> &str {
+    let path = without_fragment(href);
+
+    match path.rfind('/') {
+        Some(slash) => &path[slash + 1..],
+        None => path,
+    }
+}
+
+fn without_fragment(href: &str) -> &str {
+    match href.find('#') {
+        Some(hash) => &href[..hash],
+        None => href,
+    }
+}
+
+/// The [Levenshtein distance][lev] between two strings, measured in
+/// characters.
+///
+/// [lev]: https://en.wikipedia.org/wiki/Levenshtein_distance
+pub(crate) fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    let mut current = vec![0; right.len() + 1];
+
+    for (i, l) in left.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, r) in right.iter().enumerate() {
+            let substitution = previous[j] + if l == *r { 0 } else { 1 };
+            current[j + 1] =
+                substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[right.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_edit_distances() {
+        let inputs = vec![
+            ("", "", 0),
+            ("abc", "", 3),
+            ("kitten", "sitting", 3),
+            ("getting-started", "getting-startd", 1),
+            ("ünïcode", "unicode", 2),
+        ];
+
+        for (left, right, should_be) in inputs {
+            assert_eq!(edit_distance(left, right), should_be);
+            assert_eq!(edit_distance(right, left), should_be);
+        }
+    }
+
+    #[test]
+    fn only_suggest_plausible_candidates() {
+        let candidates = ["installation", "usage", "getting-started", "Usage"];
+
+        assert_eq!(
+            similar("Getting-Startd", candidates.iter().copied()),
+            vec!["getting-started"]
+        );
+        assert_eq!(
+            similar("usage", candidates.iter().copied()),
+            vec!["Usage", "usage"]
+        );
+        assert!(similar("license", candidates.iter().copied()).is_empty());
+    }
+
+    #[test]
+    fn suggest_sibling_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let temp = dunce::canonicalize(temp.path()).unwrap();
+        for name in &["guide.md", "README.md", "changelog.md"] {
+            std::fs::write(temp.join(name), "").unwrap();
+        }
+        let tried = vec![temp.join("gide.md")];
+
+        let got = similar_files("./gide.md#intro", &tried);
+
+        assert_eq!(got, vec!["./guide.md#intro"]);
+    }
+}
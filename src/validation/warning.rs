@@ -0,0 +1,193 @@
+use crate::validation::{redirect, Redirect};
+use reqwest::Url;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+use url::Host;
+
+/// Something about a link which works, but probably deserves a closer look.
+///
+/// The [`crate::validation::Context::severity()`] decides whether a
+/// [`Warning`] is reported as-is, treated as an error, or ignored entirely.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum Warning {
+    /// The server permanently redirected us (`301` or `308`) somewhere else,
+    /// so the link should be updated.
+    #[error("The link has permanently moved to \"{final_url}\"")]
+    PermanentRedirect {
+        /// Where the link should point instead (i.e. the end of the permanent
+        /// redirects at the start of the chain).
+        final_url: Url,
+        /// Each redirect that was followed, in order.
+        redirects: Vec<Redirect>,
+    },
+    /// The link uses `http://` instead of `https://`.
+    #[error("The link uses http:// instead of https://")]
+    InsecureLink,
+    /// The server took longer than
+    /// [`crate::validation::Context::slow_response_threshold()`] to respond.
+    #[error("The server took {:.1}s to respond", .elapsed.as_secs_f64())]
+    SlowResponse {
+        /// How long the request took.
+        elapsed: Duration,
+    },
+    /// The fragment only matches an anchor when case is ignored, even though
+    /// browsers treat fragments as case-sensitive.
+    #[error(
+        "The anchor is actually \"{anchor}\" (fragments are case-sensitive)"
+    )]
+    CaseInsensitiveFragment {
+        /// The anchor which was matched.
+        anchor: String,
+    },
+    /// The anchor is only defined using `<a name="...">`, which is obsolete
+    /// in HTML5.
+    #[error("The \"{anchor}\" anchor is only defined using an obsolete <a name=\"...\">")]
+    DeprecatedAnchor {
+        /// The anchor which was matched.
+        anchor: String,
+    },
+}
+
+impl Warning {
+    /// Create a [`Warning::PermanentRedirect`] if a chain of redirects
+    /// starts with a permanent redirect.
+    pub(crate) fn permanent_redirect(
+        redirects: Vec<Redirect>,
+    ) -> Option<Warning> {
+        let final_url = redirect::permanent_destination(&redirects)?.clone();
+
+        Some(Warning::PermanentRedirect {
+            final_url,
+            redirects,
+        })
+    }
+
+    /// What sort of [`Warning`] is this?
+    pub fn kind(&self) -> WarningKind {
+        match self {
+            Warning::PermanentRedirect { .. } => WarningKind::PermanentRedirect,
+            Warning::InsecureLink => WarningKind::InsecureLink,
+            Warning::SlowResponse { .. } => WarningKind::SlowResponse,
+            Warning::CaseInsensitiveFragment { .. } => {
+                WarningKind::CaseInsensitiveFragment
+            },
+            Warning::DeprecatedAnchor { .. } => WarningKind::DeprecatedAnchor,
+        }
+    }
+
+    /// The redirects which were followed to reach the resource, if any.
+    pub fn redirects(&self) -> &[Redirect] {
+        match self {
+            Warning::PermanentRedirect { redirects, .. } => redirects,
+            _ => &[],
+        }
+    }
+}
+
+/// Does this [`Url`] use plain `http://` to talk to something other than the
+/// local machine?
+pub(crate) fn is_insecure(url: &Url) -> bool {
+    let local = match url.host() {
+        Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback(),
+        None => true,
+    };
+
+    url.scheme() == "http" && !local
+}
+
+/// The different sorts of [`Warning`], without any of their details.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// [`Warning::PermanentRedirect`].
+    PermanentRedirect,
+    /// [`Warning::InsecureLink`].
+    InsecureLink,
+    /// [`Warning::SlowResponse`].
+    SlowResponse,
+    /// [`Warning::CaseInsensitiveFragment`].
+    CaseInsensitiveFragment,
+    /// [`Warning::DeprecatedAnchor`].
+    DeprecatedAnchor,
+}
+
+impl WarningKind {
+    /// How seriously this kind of [`Warning`] is taken when the
+    /// [`crate::validation::Context`] doesn't say otherwise.
+    ///
+    /// A fragment which only matches when case is ignored won't scroll to
+    /// anything in a browser, so it is still an error unless you opt out.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            WarningKind::CaseInsensitiveFragment => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl Display for WarningKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WarningKind::PermanentRedirect => "permanent-redirect",
+            WarningKind::InsecureLink => "insecure-link",
+            WarningKind::SlowResponse => "slow-response",
+            WarningKind::CaseInsensitiveFragment => "case-insensitive-fragment",
+            WarningKind::DeprecatedAnchor => "deprecated-anchor",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// How seriously a [`Warning`] should be taken.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Severity {
+    /// Pretend nothing happened and treat the link as valid.
+    Allow,
+    /// Report the [`Warning`] without treating the link as broken.
+    #[default]
+    Warning,
+    /// Treat the link as broken.
+    Error,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_http_is_only_insecure_for_remote_hosts() {
+        let inputs = vec![
+            ("http://example.com/", true),
+            ("https://example.com/", false),
+            ("http://localhost:8080/", false),
+            ("http://127.0.0.1/", false),
+            ("http://[::1]/", false),
+        ];
+
+        for (url, should_be) in inputs {
+            let url: Url = url.parse().unwrap();
+            assert_eq!(is_insecure(&url), should_be, "{}", url);
+        }
+    }
+}
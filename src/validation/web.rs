@@ -1,9 +1,27 @@
-use crate::validation::{CacheEntry, Context, Reason};
-use http::HeaderMap;
+use crate::validation::{
+    anchors::{html_anchors, Anchors},
+    escalate_warnings,
+    limits::Limiter,
+    redirect::{self, MAX_REDIRECTS},
+    retry::retry_after,
+    CacheEntry, Context, FailureKind, Reason, Redirect, Warning, WebError,
+    WebErrorKind,
+};
+use futures::Future;
+use http::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    HeaderMap, HeaderValue, Method, StatusCode,
+};
 use kuchiki::parse_html;
 use kuchiki::traits::TendrilSink;
-use reqwest::{Client, Response, Url};
-use std::time::SystemTime;
+use reqwest::{Client, RequestBuilder, Response, Url};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::OnceCell;
 
 /// Send a GET request to a particular endpoint.
 pub async fn get(
@@ -36,50 +54,690 @@ pub async fn head(
 }
 
 /// Check whether a [`Url`] points to a valid resource on the internet.
+///
+/// A [`Warning`] only makes the link invalid when the
+/// [`Context::severity()`] says it is an error.
 pub async fn check_web<C>(url: &Url, ctx: &C) -> Result<(), Reason>
+where
+    C: Context + ?Sized,
+{
+    let (warnings, _checked) =
+        check_web_in_session(url, ctx, &Session::new(ctx)).await?;
+
+    escalate_warnings(url.as_str(), warnings, ctx)
+}
+
+/// How a web link was checked.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct WebCheck {
+    /// How many requests were sent, including retries (zero when the result
+    /// came from the [`crate::validation::Cache`]).
+    pub attempts: u32,
+    /// When the result was originally found, if it came from the
+    /// [`crate::validation::Cache`] instead of a fresh request.
+    pub cached_at: Option<SystemTime>,
+}
+
+impl WebCheck {
+    /// Did the result come from the [`crate::validation::Cache`]?
+    pub fn is_cached(&self) -> bool { self.cached_at.is_some() }
+}
+
+/// Somewhere to put the result of checking a [`Url`] (including any
+/// [`Warning`]s) once it is known.
+type ResultSlot = OnceCell<Result<(Vec<Warning>, WebCheck), Reason>>;
+
+/// Somewhere to put a page once it has been fetched and its anchors found.
+type AnchorsSlot = OnceCell<Result<Arc<Page>, Reason>>;
+
+/// State shared by every web check in a single call to
+/// [`crate::validate()`].
+#[derive(Debug)]
+pub(crate) struct Session {
+    limiter: Limiter,
+    results: Mutex<HashMap<Url, Arc<ResultSlot>>>,
+    anchors: Mutex<HashMap<Url, Arc<AnchorsSlot>>>,
+}
+
+impl Session {
+    pub(crate) fn new<C>(ctx: &C) -> Self
+    where
+        C: Context + ?Sized,
+    {
+        Session {
+            limiter: Limiter::new(ctx.concurrency()),
+            results: Mutex::new(HashMap::new()),
+            anchors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the slot used to store the result of checking a [`Url`].
+    fn result_slot(&self, url: &Url) -> Arc<ResultSlot> {
+        let mut results = self.results.lock().expect("Mutex was poisoned");
+        Arc::clone(results.entry(url.clone()).or_default())
+    }
+
+    /// Get the slot used to store the anchors defined by a page.
+    fn anchors_slot(&self, url: &Url) -> Arc<AnchorsSlot> {
+        let mut anchors = self.anchors.lock().expect("Mutex was poisoned");
+        Arc::clone(anchors.entry(url.clone()).or_default())
+    }
+
+    /// The anchors defined by a page, if we already know them (i.e. it was
+    /// downloaded earlier in this session or the [`Context::cache()`] has
+    /// them).
+    pub(crate) fn known_anchors<C>(&self, url: &Url, ctx: &C) -> Option<Anchors>
+    where
+        C: Context + ?Sized,
+    {
+        let slot = self
+            .anchors
+            .lock()
+            .expect("Mutex was poisoned")
+            .get(url)
+            .cloned();
+
+        if let Some(Ok(page)) = slot.as_deref().and_then(OnceCell::get) {
+            return page.anchors.clone();
+        }
+
+        ctx.cache()?.lookup(url).and_then(cached_anchors)
+    }
+}
+
+/// Check a [`Url`], sharing the result with any other checks for the same
+/// [`Url`] in this [`Session`].
+///
+/// On success, this returns any [`Warning`]s about the [`Url`] (e.g. because
+/// it was permanently redirected somewhere else) and how it was checked.
+///
+/// Parsing a [`Url`] normalizes it, so two links which are written slightly
+/// differently (e.g. `HTTPS://Example.COM:443/` and `https://example.com/`)
+/// will still only be checked once.
+pub(crate) async fn check_web_in_session<C>(
+    url: &Url,
+    ctx: &C,
+    session: &Session,
+) -> Result<(Vec<Warning>, WebCheck), Reason>
+where
+    C: Context + ?Sized,
+{
+    let slot = session.result_slot(url);
+
+    if slot.initialized() {
+        log::debug!("\"{}\" has already been checked", url);
+    }
+
+    match slot.get_or_init(|| check_once(url, ctx, session)).await {
+        Ok((warnings, check)) => Ok((warnings.clone(), *check)),
+        Err(reason) => Err(duplicate(reason)),
+    }
+}
+
+/// Make a copy of a [`Reason`] produced while checking a [`Url`] so it can be
+/// handed to every [`crate::Link`] which shared the request.
+fn duplicate(reason: &Reason) -> Reason {
+    match reason {
+        Reason::Web(error) => Reason::Web(error.clone()),
+        Reason::Dom => Reason::Dom,
+        Reason::Cached {
+            failure,
+            checked_at,
+        } => Reason::Cached {
+            failure: *failure,
+            checked_at: *checked_at,
+        },
+        // web checks don't fail for any other reasons
+        other => Reason::Io(io::Error::other(other.to_string())),
+    }
+}
+
+async fn check_once<C>(
+    url: &Url,
+    ctx: &C,
+    session: &Session,
+) -> Result<(Vec<Warning>, WebCheck), Reason>
 where
     C: Context + ?Sized,
 {
     log::debug!("Checking \"{}\" on the web", url);
 
-    if already_valid(&url, ctx) {
-        log::debug!("The cache says \"{}\" is still valid", url);
-        return Ok(());
-    }
-
-    let result = if let Some(fragment) = url.fragment() {
-        log::debug!("Checking \"{}\" contains \"{}\"", url, fragment);
-        let response =
-            get(ctx.client(), url.clone(), ctx.url_specific_headers(&url))
-                .await?;
-        let document = parse_html()
-            .from_utf8()
-            .read_from(&mut response.text().await?.as_bytes())?;
-        document
-            .select_first(&format!("#{}", fragment))
-            .map(|_| ())
-            .map_err(|_| Reason::Dom)
-    } else {
-        head(ctx.client(), url.clone(), ctx.url_specific_headers(&url))
+    if let Some(entry) = unexpired_cache_entry(url, ctx) {
+        if entry.valid {
+            log::debug!("The cache says \"{}\" is still valid", url);
+            let check = WebCheck {
+                attempts: 0,
+                cached_at: Some(entry.timestamp),
+            };
+            return Ok((entry.warnings, check));
+        }
+
+        let failure = entry.failure.unwrap_or(FailureKind::Other);
+        log::debug!("The cache says \"{}\" is broken ({})", url, failure);
+        return Err(Reason::Cached {
+            failure,
+            checked_at: entry.timestamp,
+        });
+    }
+
+    let result = match url.fragment() {
+        Some(fragment) => {
+            log::debug!("Checking \"{}\" contains \"{}\"", url, fragment);
+            // the anchors belong to the page itself
+            let mut page_url = url.clone();
+            page_url.set_fragment(None);
+
+            match page_anchors(&page_url, ctx, session).await {
+                Ok(page) => match page.find(fragment) {
+                    Some(warnings) => {
+                        let warnings = page
+                            .warnings
+                            .iter()
+                            .cloned()
+                            .chain(warnings)
+                            .collect();
+                        Ok((warnings, page.check()))
+                    },
+                    None => Err(Reason::Dom),
+                },
+                Err(reason) => Err(reason),
+            }
+        },
+        None => {
+            let previous = previous_page_entry(url, ctx);
+            with_retries(url, ctx, session, || {
+                check_exists(url, ctx, previous.as_ref())
+            })
             .await
-            .map_err(Reason::from)
+            .map(|(mut page, attempts)| {
+                page.attempts = attempts;
+                let checked = (page.warnings.clone(), page.check());
+                update_cache(url, ctx, page.into_cache_entry());
+                checked
+            })
+        },
     };
 
-    let entry = CacheEntry::new(SystemTime::now(), result.is_ok());
-    update_cache(url, ctx, entry);
+    match result {
+        Ok(_) if url.fragment().is_none() => {},
+        Ok((ref warnings, _)) => {
+            let entry = CacheEntry {
+                warnings: warnings.clone(),
+                ..CacheEntry::new(SystemTime::now(), true)
+            };
+            update_cache(url, ctx, entry);
+        },
+        Err(ref reason) => {
+            let failure = failure_kind(reason);
+            update_cache(
+                url,
+                ctx,
+                CacheEntry::failed(SystemTime::now(), failure),
+            );
+        },
+    }
 
     result
 }
 
-fn already_valid<C>(url: &Url, ctx: &C) -> bool
+/// Get the anchors defined by a page, making sure we only download and parse
+/// it once per [`Session`].
+async fn page_anchors<C>(
+    url: &Url,
+    ctx: &C,
+    session: &Session,
+) -> Result<Arc<Page>, Reason>
+where
+    C: Context + ?Sized,
+{
+    let slot = session.anchors_slot(url);
+
+    match slot.get_or_init(|| load_anchors(url, ctx, session)).await {
+        Ok(page) => Ok(Arc::clone(page)),
+        Err(reason) => Err(duplicate(reason)),
+    }
+}
+
+async fn load_anchors<C>(
+    url: &Url,
+    ctx: &C,
+    session: &Session,
+) -> Result<Arc<Page>, Reason>
+where
+    C: Context + ?Sized,
+{
+    let cached = unexpired_cache_entry(url, ctx)
+        .filter(|entry| entry.valid && entry.anchors.is_some());
+
+    if let Some(entry) = cached {
+        log::debug!("Using the cached anchors for \"{}\"", url);
+        return Ok(Arc::new(Page::from_cache_entry(entry)));
+    }
+
+    let previous = previous_page_entry(url, ctx);
+    let (mut page, attempts) = with_retries(url, ctx, session, || {
+        fetch_anchors(url, ctx, previous.as_ref())
+    })
+    .await?;
+    page.attempts = attempts;
+
+    update_cache(url, ctx, page.clone().into_cache_entry());
+
+    Ok(Arc::new(page))
+}
+
+/// What we learned about a page from a successful request.
+#[derive(Debug, Clone)]
+struct Page {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    anchors: Option<Anchors>,
+    /// Any [`Warning`]s about the page itself (e.g. a slow response).
+    warnings: Vec<Warning>,
+    /// How many requests it took to get the page (zero if it came from the
+    /// cache).
+    attempts: u32,
+    /// When the page was cached, if it came from the cache.
+    cached_at: Option<SystemTime>,
+}
+
+impl Page {
+    /// Read the cache validators from a response, falling back to the ones
+    /// from the previous request if the server sent a `304 Not Modified`
+    /// without them.
+    fn from_response(
+        response: &Response,
+        warnings: Vec<Warning>,
+        previous: Option<&CacheEntry>,
+    ) -> Self {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(String::from)
+        };
+        let not_modified = response.status() == StatusCode::NOT_MODIFIED;
+        let previous = previous.filter(|_| not_modified);
+
+        Page {
+            etag: header(ETAG).or_else(|| previous?.etag.clone()),
+            last_modified: header(LAST_MODIFIED)
+                .or_else(|| previous?.last_modified.clone()),
+            // the page hasn't changed, so neither have its anchors
+            anchors: previous.and_then(cached_anchors),
+            warnings,
+            attempts: 1,
+            cached_at: None,
+        }
+    }
+
+    fn from_cache_entry(entry: CacheEntry) -> Self {
+        Page {
+            anchors: cached_anchors(&entry),
+            etag: entry.etag,
+            last_modified: entry.last_modified,
+            warnings: entry.warnings,
+            attempts: 0,
+            cached_at: Some(entry.timestamp),
+        }
+    }
+
+    fn check(&self) -> WebCheck {
+        WebCheck {
+            attempts: self.attempts,
+            cached_at: self.cached_at,
+        }
+    }
+
+    /// Look for the anchor a fragment refers to (see [`Anchors::find()`]).
+    fn find(&self, fragment: &str) -> Option<Vec<Warning>> {
+        self.anchors.as_ref()?.find(fragment)
+    }
+
+    fn into_cache_entry(self) -> CacheEntry {
+        let (anchors, deprecated_anchors) = match self.anchors {
+            Some(anchors) => (Some(anchors.all), Some(anchors.deprecated)),
+            None => (None, None),
+        };
+
+        CacheEntry {
+            etag: self.etag,
+            last_modified: self.last_modified,
+            anchors,
+            deprecated_anchors,
+            warnings: self.warnings,
+            ..CacheEntry::new(SystemTime::now(), true)
+        }
+    }
+}
+
+fn cached_anchors(entry: &CacheEntry) -> Option<Anchors> {
+    Some(Anchors {
+        all: entry.anchors.clone()?,
+        deprecated: entry.deprecated_anchors.clone().unwrap_or_default(),
+    })
+}
+
+/// Add `If-None-Match` and `If-Modified-Since` headers so the server can tell
+/// us when a page hasn't changed since we last saw it.
+fn conditional(
+    mut request: RequestBuilder,
+    previous: Option<&CacheEntry>,
+) -> RequestBuilder {
+    if let Some(previous) = previous {
+        if let Some(ref etag) = previous.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(ref last_modified) = previous.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    request
+}
+
+/// Summarise a [`Reason`] so it can be stored in the
+/// [`crate::validation::Cache`].
+fn failure_kind(reason: &Reason) -> FailureKind {
+    match reason {
+        Reason::Web(error) => match (error.status(), error.kind()) {
+            (Some(status), _) => FailureKind::Status(status.as_u16()),
+            (None, WebErrorKind::Timeout) => FailureKind::Timeout,
+            (None, WebErrorKind::Dns)
+            | (None, WebErrorKind::Tls)
+            | (None, WebErrorKind::ConnectionRefused)
+            | (None, WebErrorKind::Connect) => FailureKind::Connect,
+            (None, _) => FailureKind::Other,
+        },
+        Reason::Dom => FailureKind::MissingAnchor,
+        Reason::Cached { failure, .. } => *failure,
+        _ => FailureKind::Other,
+    }
+}
+
+/// How should we check whether a [`Url`] (without a fragment) exists?
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum MethodPolicy {
+    /// Send a `HEAD` request, retrying with `GET` if the server responds with
+    /// a status code that suggests it doesn't support `HEAD` (`403 Forbidden`,
+    /// `405 Method Not Allowed`, or `501 Not Implemented`).
+    #[default]
+    HeadWithGetFallback,
+    /// Only ever send a `HEAD` request.
+    HeadOnly,
+    /// Always send a `GET` request.
+    Get,
+}
+
+impl MethodPolicy {
+    /// Does this status code mean a server may have rejected our `HEAD`
+    /// request even though a `GET` would have worked?
+    pub fn head_was_rejected(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::FORBIDDEN
+                | StatusCode::METHOD_NOT_ALLOWED
+                | StatusCode::NOT_IMPLEMENTED
+        )
+    }
+}
+
+/// A failed request.
+#[derive(Debug)]
+struct Failure {
+    error: WebError,
+    /// How long the server asked us to wait before trying again.
+    retry_after: Option<Duration>,
+}
+
+impl From<reqwest::Error> for Failure {
+    fn from(error: reqwest::Error) -> Self {
+        Failure {
+            error: WebError::new(error),
+            retry_after: None,
+        }
+    }
+}
+
+/// Send a request, following any redirects ourselves so we know where we
+/// ended up, and turning error status codes into a [`Failure`].
+///
+/// Successful requests come back with [`Warning`]s about permanent redirects
+/// and slow responses. Redirects are only seen here if [`Context::client()`]
+/// doesn't already follow them.
+async fn send<C>(
+    ctx: &C,
+    mut method: Method,
+    url: &Url,
+    previous: Option<&CacheEntry>,
+) -> Result<(Response, Vec<Warning>), Failure>
 where
     C: Context + ?Sized,
 {
-    if let Some(cache) = ctx.cache() {
-        return cache.url_is_still_valid(url, ctx.cache_timeout());
+    let started = Instant::now();
+    let mut redirects = Vec::new();
+    let mut current = url.clone();
+
+    loop {
+        let request = ctx
+            .client()
+            .request(method.clone(), current.clone())
+            .headers(ctx.url_specific_headers(&current));
+
+        let response = match conditional(request, previous).send().await {
+            Ok(response) => response,
+            Err(error) => {
+                return Err(Failure {
+                    error: WebError::new(error).with_redirects(redirects),
+                    retry_after: None,
+                })
+            },
+        };
+
+        if let Some(next) = redirect::location(&response) {
+            if redirects.len() >= MAX_REDIRECTS {
+                let error = WebError::too_many_redirects(current);
+                return Err(Failure {
+                    error: error.with_redirects(redirects),
+                    retry_after: None,
+                });
+            }
+
+            let status = response.status();
+            log::debug!(
+                "\"{}\" redirects to \"{}\" ({})",
+                current,
+                next,
+                status
+            );
+
+            // "303 See Other" means the new location should be fetched with GET
+            if status == StatusCode::SEE_OTHER && method != Method::HEAD {
+                method = Method::GET;
+            }
+
+            redirects.push(Redirect {
+                from: current,
+                to: next.clone(),
+                status: status.as_u16(),
+            });
+            current = next;
+            continue;
+        }
+
+        return match response.error_for_status_ref() {
+            Ok(_) => {
+                let elapsed = started.elapsed();
+                let mut warnings: Vec<_> =
+                    Warning::permanent_redirect(redirects)
+                        .into_iter()
+                        .collect();
+
+                if elapsed > ctx.slow_response_threshold() {
+                    log::debug!("\"{}\" took {:?} to respond", url, elapsed);
+                    warnings.push(Warning::SlowResponse { elapsed });
+                }
+
+                Ok((response, warnings))
+            },
+            Err(error) => Err(Failure {
+                retry_after: retry_after(response.status(), response.headers()),
+                error: WebError::new(error).with_redirects(redirects),
+            }),
+        };
     }
+}
 
-    false
+/// Keep making requests until one succeeds or the [`Context::retry_policy()`]
+/// says we should give up, returning the number of attempts it took.
+///
+/// We only hold a permit from the [`Limiter`] while a request is in flight,
+/// so other requests can go ahead while we're waiting to retry.
+async fn with_retries<C, F, Fut, T>(
+    url: &Url,
+    ctx: &C,
+    session: &Session,
+    mut request: F,
+) -> Result<(T, u32), Reason>
+where
+    C: Context + ?Sized,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    let policy = ctx.retry_policy(url);
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        let outcome = {
+            let _permit = session.limiter.acquire(url, ctx).await;
+            request().await
+        };
+
+        let failure = match outcome {
+            Ok(value) => return Ok((value, attempts)),
+            Err(failure) => failure,
+        };
+
+        let retryable = failure
+            .error
+            .inner()
+            .map(|error| policy.should_retry(error))
+            .unwrap_or(false);
+
+        let delay = if attempts < policy.max_attempts && retryable {
+            policy.delay(attempts, failure.retry_after)
+        } else {
+            None
+        };
+
+        match delay {
+            Some(delay) => {
+                log::debug!(
+                    "Attempt {} for \"{}\" failed ({}), retrying in {:?}",
+                    attempts,
+                    url,
+                    failure.error,
+                    delay,
+                );
+                tokio::time::sleep(delay).await;
+            },
+            None => {
+                return Err(Reason::Web(failure.error.with_attempts(attempts)))
+            },
+        }
+    }
+}
+
+/// Download a page and find all the anchors it defines.
+async fn fetch_anchors<C>(
+    url: &Url,
+    ctx: &C,
+    previous: Option<&CacheEntry>,
+) -> Result<Page, Failure>
+where
+    C: Context + ?Sized,
+{
+    // a 304 is only useful if we know which anchors the page had last time
+    let previous = previous.filter(|entry| entry.anchors.is_some());
+
+    let (response, warnings) = send(ctx, Method::GET, url, previous).await?;
+    let mut page = Page::from_response(&response, warnings, previous);
+
+    if page.anchors.is_none() {
+        let document = parse_html().one(response.text().await?);
+        page.anchors = Some(html_anchors(&document));
+    } else {
+        log::debug!("\"{}\" hasn't changed since it was last checked", url);
+    }
+
+    Ok(page)
+}
+
+async fn check_exists<C>(
+    url: &Url,
+    ctx: &C,
+    previous: Option<&CacheEntry>,
+) -> Result<Page, Failure>
+where
+    C: Context + ?Sized,
+{
+    // we drop the response as soon as we've got the status code so a GET
+    // doesn't download the entire body
+    let request = |method| async move {
+        let (response, warnings) = send(ctx, method, url, previous).await?;
+        Ok(Page::from_response(&response, warnings, previous))
+    };
+
+    match ctx.method_policy(url) {
+        MethodPolicy::HeadOnly => request(Method::HEAD).await,
+        MethodPolicy::Get => request(Method::GET).await,
+        MethodPolicy::HeadWithGetFallback => {
+            match request(Method::HEAD).await {
+                Err(failure)
+                    if failure
+                        .error
+                        .status()
+                        .map(MethodPolicy::head_was_rejected)
+                        .unwrap_or(false) =>
+                {
+                    log::debug!(
+                        "\"{}\" rejected HEAD ({}), trying GET",
+                        url,
+                        failure.error,
+                    );
+                    request(Method::GET).await
+                },
+                other => other,
+            }
+        },
+    }
+}
+
+/// Find the last [`CacheEntry`] for a page which existed, even if it is stale.
+fn previous_page_entry<C>(url: &Url, ctx: &C) -> Option<CacheEntry>
+where
+    C: Context + ?Sized,
+{
+    ctx.cache()?
+        .lookup(url)
+        .filter(|entry| entry.valid)
+        .cloned()
+}
+
+fn unexpired_cache_entry<C>(url: &Url, ctx: &C) -> Option<CacheEntry>
+where
+    C: Context + ?Sized,
+{
+    ctx.cache()?
+        .lookup_unexpired(
+            url,
+            ctx.cache_timeout(),
+            ctx.negative_cache_timeout(),
+        )
+        .cloned()
 }
 
 fn update_cache<C>(url: &Url, ctx: &C, entry: CacheEntry)
@@ -90,3 +748,579 @@ where
         cache.insert(url.clone(), entry);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::{BasicContext, Cache, RetryPolicy, Warning};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex, MutexGuard},
+    };
+
+    /// Start a tiny HTTP server which replies to each method with a fixed
+    /// status code, recording the methods it was sent.
+    fn server(
+        head_status: u16,
+        get_status: u16,
+    ) -> (Url, Arc<Mutex<Vec<String>>>) {
+        server_with(move |request, _| {
+            if request.starts_with("HEAD ") {
+                Reply::status(head_status)
+            } else {
+                Reply::status(get_status)
+            }
+        })
+    }
+
+    #[derive(Debug, Default)]
+    struct Reply {
+        status: u16,
+        headers: String,
+        body: String,
+    }
+
+    impl Reply {
+        fn status(status: u16) -> Self {
+            Reply {
+                status,
+                ..Default::default()
+            }
+        }
+
+        fn header(mut self, name: &str, value: &str) -> Self {
+            self.headers.push_str(&format!("{}: {}\r\n", name, value));
+            self
+        }
+
+        fn body(mut self, body: &str) -> Self {
+            self.body = body.to_string();
+            self
+        }
+    }
+
+    /// Start a tiny HTTP server which uses a callback to decide how to reply
+    /// to the n'th request, given the request line and headers.
+    fn server_with<F>(respond: F) -> (Url, Arc<Mutex<Vec<String>>>)
+    where
+        F: Fn(&str, usize) -> Reply + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let methods = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&methods);
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0
+                        || line == "\r\n"
+                    {
+                        break;
+                    }
+                    request.push_str(&line);
+                }
+
+                let method = request
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+                let mut seen = seen.lock().unwrap();
+                let reply = respond(&request, seen.len());
+                seen.push(method);
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    reply.status,
+                    reply.headers,
+                    reply.body.len(),
+                    reply.body,
+                )
+                .unwrap();
+            }
+        });
+
+        (url.parse().unwrap(), methods)
+    }
+
+    #[derive(Debug)]
+    struct PolicyContext {
+        inner: BasicContext,
+        policy: MethodPolicy,
+        retry: RetryPolicy,
+    }
+
+    impl PolicyContext {
+        fn new(policy: MethodPolicy) -> Self {
+            let client = Client::builder()
+                .no_proxy()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap();
+            PolicyContext {
+                inner: BasicContext::with_client(client),
+                policy,
+                retry: RetryPolicy::default(),
+            }
+        }
+    }
+
+    impl Context for PolicyContext {
+        fn client(&self) -> &Client { self.inner.client() }
+
+        fn filesystem_options(&self) -> &crate::validation::Options {
+            self.inner.filesystem_options()
+        }
+
+        fn method_policy(&self, _url: &Url) -> MethodPolicy { self.policy }
+
+        fn retry_policy(&self, _url: &Url) -> RetryPolicy { self.retry.clone() }
+
+        fn cache(&self) -> Option<MutexGuard<'_, Cache>> { self.inner.cache() }
+
+        fn slow_response_threshold(&self) -> Duration {
+            self.inner.slow_response_threshold()
+        }
+    }
+
+    #[tokio::test]
+    async fn fall_back_to_get_when_head_is_not_allowed() {
+        let (url, methods) = server(405, 200);
+        let ctx = PolicyContext::new(MethodPolicy::HeadWithGetFallback);
+
+        check_web(&url, &ctx).await.unwrap();
+
+        assert_eq!(*methods.lock().unwrap(), vec!["HEAD", "GET"]);
+    }
+
+    #[tokio::test]
+    async fn dont_fall_back_on_a_genuine_404() {
+        let (url, methods) = server(404, 200);
+        let ctx = PolicyContext::new(MethodPolicy::HeadWithGetFallback);
+
+        assert!(check_web(&url, &ctx).await.is_err());
+
+        assert_eq!(*methods.lock().unwrap(), vec!["HEAD"]);
+    }
+
+    #[tokio::test]
+    async fn head_only_never_sends_get() {
+        let (url, methods) = server(405, 200);
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+
+        assert!(check_web(&url, &ctx).await.is_err());
+
+        assert_eq!(*methods.lock().unwrap(), vec!["HEAD"]);
+    }
+
+    #[tokio::test]
+    async fn get_policy_skips_head() {
+        let (url, methods) = server(405, 200);
+        let ctx = PolicyContext::new(MethodPolicy::Get);
+
+        check_web(&url, &ctx).await.unwrap();
+
+        assert_eq!(*methods.lock().unwrap(), vec!["GET"]);
+    }
+
+    fn quick_retries(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_transient_failures() {
+        let (url, methods) =
+            server_with(|_, n| Reply::status(if n < 2 { 502 } else { 200 }));
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = quick_retries(3);
+
+        let (_, check) = check_web_in_session(&url, &ctx, &Session::new(&ctx))
+            .await
+            .unwrap();
+
+        assert_eq!(check.attempts, 3);
+        assert_eq!(methods.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn give_up_after_the_maximum_number_of_attempts() {
+        let (url, methods) = server(503, 503);
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = quick_retries(3);
+
+        let err = check_web(&url, &ctx).await.unwrap_err();
+
+        assert_eq!(err.web_error().unwrap().attempts(), 3);
+        assert_eq!(methods.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn give_up_when_asked_to_wait_too_long() {
+        let (url, methods) = server_with(|_, _| {
+            Reply::status(429).header("Retry-After", "3600")
+        });
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = quick_retries(3);
+
+        let err = check_web(&url, &ctx).await.unwrap_err();
+
+        assert_eq!(err.web_error().unwrap().attempts(), 1);
+        assert_eq!(methods.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn report_dead_links_from_the_cache() {
+        let (url, methods) = server(404, 404);
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+
+        let first = check_web(&url, &ctx).await.unwrap_err();
+        let second = check_web(&url, &ctx).await.unwrap_err();
+
+        assert!(matches!(first, Reason::Web(_)));
+        assert!(matches!(
+            second,
+            Reason::Cached {
+                failure: FailureKind::Status(404),
+                ..
+            }
+        ));
+        assert_eq!(methods.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn say_whether_valid_results_were_cached() {
+        let (url, methods) = server(200, 200);
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+
+        let (_, fresh) = check_web_in_session(&url, &ctx, &Session::new(&ctx))
+            .await
+            .unwrap();
+        let (_, cached) = check_web_in_session(&url, &ctx, &Session::new(&ctx))
+            .await
+            .unwrap();
+
+        assert_eq!(fresh.attempts, 1);
+        assert!(!fresh.is_cached());
+        assert_eq!(cached.attempts, 0);
+        assert!(cached.is_cached());
+        assert_eq!(methods.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn temporary_failures_are_always_rechecked() {
+        let (url, methods) = server(503, 503);
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = RetryPolicy::never();
+
+        check_web(&url, &ctx).await.unwrap_err();
+        let second = check_web(&url, &ctx).await.unwrap_err();
+
+        assert!(matches!(second, Reason::Web(_)));
+        assert_eq!(methods.lock().unwrap().len(), 2);
+    }
+
+    /// Pretend every entry in the cache was created a long time ago.
+    fn make_cache_stale(ctx: &PolicyContext) {
+        let mut cache = ctx.cache().unwrap();
+        let stale: Vec<_> = cache
+            .iter()
+            .map(|(url, entry)| {
+                let entry = CacheEntry {
+                    timestamp: SystemTime::UNIX_EPOCH,
+                    ..entry.clone()
+                };
+                (url.clone(), entry)
+            })
+            .collect();
+        cache.extend(stale);
+    }
+
+    #[tokio::test]
+    async fn revalidate_stale_pages_with_their_etag() {
+        let (url, methods) = server_with(|request, _| {
+            if request.contains("if-none-match: \"v1\"") {
+                Reply::status(304)
+            } else {
+                Reply::status(200)
+                    .header("ETag", "\"v1\"")
+                    .body(r#"<h1 id="first">First</h1>"#)
+            }
+        });
+        let ctx = PolicyContext::new(MethodPolicy::HeadWithGetFallback);
+        let first = url.join("#first").unwrap();
+        let second = url.join("#second").unwrap();
+
+        check_web(&first, &ctx).await.unwrap();
+        make_cache_stale(&ctx);
+        check_web(&first, &ctx).await.unwrap();
+        let err = check_web(&second, &ctx).await.unwrap_err();
+
+        assert!(matches!(err, Reason::Dom));
+        // the last check can reuse the cached anchors without a request
+        assert_eq!(*methods.lock().unwrap(), vec!["GET", "GET"]);
+        let cache = ctx.cache().unwrap();
+        let page = cache.lookup(&url).unwrap();
+        assert_eq!(page.etag.as_deref(), Some("\"v1\""));
+        assert!(page.anchors.as_ref().unwrap().contains("first"));
+    }
+
+    #[tokio::test]
+    async fn fragments_with_the_wrong_case_are_broken_by_default() {
+        let (url, _) = server_with(|_, _| {
+            Reply::status(200).body(r#"<h1 id="first">First</h1>"#)
+        });
+        let ctx = PolicyContext::new(MethodPolicy::HeadWithGetFallback);
+
+        let err = check_web(&url.join("#FIRST").unwrap(), &ctx)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            Reason::Warning(Warning::CaseInsensitiveFragment { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn not_modified_means_still_valid() {
+        let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let (url, methods) = server_with(move |request, _| {
+            if request.contains("if-modified-since: ") {
+                Reply::status(304)
+            } else {
+                Reply::status(200).header("Last-Modified", last_modified)
+            }
+        });
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+
+        check_web(&url, &ctx).await.unwrap();
+        make_cache_stale(&ctx);
+        check_web(&url, &ctx).await.unwrap();
+
+        assert_eq!(methods.lock().unwrap().len(), 2);
+        let cache = ctx.cache().unwrap();
+        let entry = cache.lookup(&url).unwrap();
+        assert_eq!(entry.last_modified.as_deref(), Some(last_modified));
+        assert!(entry.timestamp > SystemTime::UNIX_EPOCH);
+    }
+
+    #[tokio::test]
+    async fn concurrent_checks_of_the_same_url_share_a_request() {
+        let (url, methods) = server(503, 503);
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = RetryPolicy::never();
+        let session = Session::new(&ctx);
+        let same_url: Url = url.as_str().to_uppercase().parse().unwrap();
+        assert_eq!(url, same_url);
+
+        let (first, second) = futures::join!(
+            check_web_in_session(&url, &ctx, &session),
+            check_web_in_session(&same_url, &ctx, &session),
+        );
+
+        assert!(matches!(first, Err(Reason::Web(_))));
+        assert!(matches!(second, Err(Reason::Web(_))));
+        assert_eq!(methods.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn links_from_different_directories_share_a_request() {
+        let (url, methods) = server(503, 503);
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = RetryPolicy::never();
+        let mut files = codespan::Files::new();
+        let file = files.add("README.md", "");
+        let links = ["docs", "examples"].iter().map(|directory| {
+            let link = crate::Link::new(
+                url.to_string(),
+                Default::default(),
+                file,
+                "README.md".into(),
+            );
+            (std::path::PathBuf::from(directory), link)
+        });
+
+        let got = crate::validation::validate_in_directories(links, &ctx).await;
+
+        assert_eq!(got.invalid.len(), 2);
+        assert_eq!(methods.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fragments_on_the_same_page_share_a_download() {
+        let (url, methods) = server_with(|_, _| {
+            Reply::status(200).body(r#"<h1 id="push">push()</h1>"#)
+        });
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        let session = Session::new(&ctx);
+        let push = url.join("#push").unwrap();
+        let pop = url.join("#pop").unwrap();
+
+        let (push, pop) = futures::join!(
+            check_web_in_session(&push, &ctx, &session),
+            check_web_in_session(&pop, &ctx, &session),
+        );
+
+        assert!(push.is_ok());
+        assert!(matches!(pop, Err(Reason::Dom)));
+        assert_eq!(*methods.lock().unwrap(), vec!["GET"]);
+    }
+
+    #[tokio::test]
+    async fn classify_missing_pages() {
+        let (url, _) = server(410, 410);
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+
+        let err = check_web(&url, &ctx).await.unwrap_err();
+
+        assert_eq!(err.web_error_kind(), Some(WebErrorKind::Gone));
+        assert_eq!(err.status(), Some(StatusCode::GONE));
+        assert_eq!(err.final_url(), Some(&url));
+        assert_eq!(err.to_string(), "The page has been removed (410 Gone)");
+    }
+
+    #[tokio::test]
+    async fn classify_refused_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url: Url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        drop(listener);
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = RetryPolicy::never();
+
+        let err = check_web(&url, &ctx).await.unwrap_err();
+
+        assert_eq!(err.web_error_kind(), Some(WebErrorKind::ConnectionRefused));
+        assert_eq!(err.status(), None);
+    }
+
+    #[tokio::test]
+    async fn classify_unknown_hosts() {
+        // the ".invalid" top-level domain is guaranteed to never resolve
+        let url: Url = "http://linkcheck.invalid/".parse().unwrap();
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = RetryPolicy::never();
+
+        let err = check_web(&url, &ctx).await.unwrap_err();
+
+        assert_eq!(err.web_error_kind(), Some(WebErrorKind::Dns));
+    }
+
+    #[tokio::test]
+    async fn classify_tls_failures() {
+        // a plain HTTP server can't complete a TLS handshake
+        let (url, _) = server(200, 200);
+        let url: Url =
+            url.as_str().replacen("http", "https", 1).parse().unwrap();
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.retry = RetryPolicy::never();
+
+        let err = check_web(&url, &ctx).await.unwrap_err();
+
+        assert_eq!(err.web_error_kind(), Some(WebErrorKind::Tls));
+    }
+
+    #[tokio::test]
+    async fn classify_redirect_loops() {
+        let (url, _) =
+            server_with(|_, _| Reply::status(302).header("Location", "/"));
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+
+        let err = check_web(&url, &ctx).await.unwrap_err();
+
+        assert_eq!(err.web_error_kind(), Some(WebErrorKind::TooManyRedirects));
+        assert_eq!(err.web_error().unwrap().redirects().len(), MAX_REDIRECTS);
+    }
+
+    /// Redirect `/old` to `/new` with the given status code.
+    fn moved(status: u16) -> Reply {
+        Reply::status(status).header("Location", "/new")
+    }
+
+    #[tokio::test]
+    async fn record_the_redirect_chain() {
+        let (url, methods) = server_with(|request, _| {
+            if request.contains(" /old ") {
+                moved(301)
+            } else {
+                Reply::status(200)
+            }
+        });
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        let session = Session::new(&ctx);
+
+        let old = url.join("old").unwrap();
+        let (got, _) =
+            check_web_in_session(&old, &ctx, &session).await.unwrap();
+
+        assert_eq!(
+            got[0].redirects(),
+            &[Redirect {
+                from: old,
+                to: url.join("new").unwrap(),
+                status: 301,
+            }]
+        );
+        assert_eq!(*methods.lock().unwrap(), vec!["HEAD", "HEAD"]);
+    }
+
+    #[tokio::test]
+    async fn only_permanent_redirects_are_warnings() {
+        let (url, _) = server_with(|request, _| {
+            if request.contains(" /permanent ") {
+                moved(308)
+            } else if request.contains(" /temporary ") {
+                moved(307)
+            } else {
+                Reply::status(200)
+            }
+        });
+        let ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        let links = ["permanent", "temporary"]
+            .iter()
+            .map(|path| crate::tests::link(url.join(path).unwrap()));
+
+        let got = crate::validate(std::path::Path::new("."), links, &ctx).await;
+
+        assert_eq!(got.valid.len(), 1);
+        assert_eq!(got.warnings.len(), 1);
+        assert_eq!(
+            got.warnings[0].warning,
+            Warning::PermanentRedirect {
+                final_url: url.join("new").unwrap(),
+                redirects: vec![Redirect {
+                    from: url.join("permanent").unwrap(),
+                    to: url.join("new").unwrap(),
+                    status: 308,
+                }],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn warn_about_slow_responses() {
+        let (url, _) = server(200, 200);
+        let mut ctx = PolicyContext::new(MethodPolicy::HeadOnly);
+        ctx.inner = BasicContext::with_client(ctx.inner.client().clone())
+            .set_slow_response_threshold(Duration::from_secs(0));
+        let session = Session::new(&ctx);
+
+        let (got, _) =
+            check_web_in_session(&url, &ctx, &session).await.unwrap();
+
+        assert_eq!(got.len(), 1);
+        assert!(matches!(got[0], Warning::SlowResponse { .. }), "{:?}", got);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::validation::Redirect;
+use http::StatusCode;
+use reqwest::Url;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    sync::Arc,
+};
+
+/// A failed web request.
+///
+/// Unlike the underlying [`reqwest::Error`], a [`WebError`] can be cheaply
+/// cloned and tells you what sort of problem was encountered (see
+/// [`WebError::kind()`]), so "the page is gone" can be distinguished from "the
+/// server had a hiccup".
+#[derive(Debug, Clone)]
+pub struct WebError {
+    kind: WebErrorKind,
+    status: Option<StatusCode>,
+    url: Option<Box<Url>>,
+    attempts: u32,
+    redirects: Vec<Redirect>,
+    inner: Option<Arc<reqwest::Error>>,
+}
+
+impl WebError {
+    pub(crate) fn new(error: reqwest::Error) -> Self {
+        WebError {
+            kind: WebErrorKind::of(&error),
+            status: error.status(),
+            url: error.url().cloned().map(Box::new),
+            attempts: 1,
+            redirects: Vec::new(),
+            inner: Some(Arc::new(error)),
+        }
+    }
+
+    pub(crate) fn too_many_redirects(url: Url) -> Self {
+        WebError {
+            kind: WebErrorKind::TooManyRedirects,
+            status: None,
+            url: Some(Box::new(url)),
+            attempts: 1,
+            redirects: Vec::new(),
+            inner: None,
+        }
+    }
+
+    pub(crate) fn with_attempts(self, attempts: u32) -> Self {
+        WebError { attempts, ..self }
+    }
+
+    pub(crate) fn with_redirects(self, redirects: Vec<Redirect>) -> Self {
+        WebError { redirects, ..self }
+    }
+
+    /// What sort of error was this?
+    pub fn kind(&self) -> WebErrorKind { self.kind }
+
+    /// The status code sent by the server, if we got that far.
+    pub fn status(&self) -> Option<StatusCode> { self.status }
+
+    /// The URL of the final request, after following any redirects.
+    pub fn url(&self) -> Option<&Url> { self.url.as_deref() }
+
+    /// How many requests were sent before giving up.
+    pub fn attempts(&self) -> u32 { self.attempts }
+
+    /// Any redirects which were followed before the error occurred.
+    pub fn redirects(&self) -> &[Redirect] { &self.redirects }
+
+    /// Did the request time out?
+    pub fn is_timeout(&self) -> bool { self.kind == WebErrorKind::Timeout }
+
+    /// Get a reference to the underlying [`reqwest::Error`], if there was one.
+    pub fn inner(&self) -> Option<&reqwest::Error> { self.inner.as_deref() }
+}
+
+impl From<reqwest::Error> for WebError {
+    fn from(error: reqwest::Error) -> Self { WebError::new(error) }
+}
+
+impl Display for WebError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.status() {
+            Some(status) => write!(f, "{} ({})", self.kind, status),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl Error for WebError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.inner {
+            Some(ref inner) => Some(&**inner),
+            None => None,
+        }
+    }
+}
+
+/// The broad categories a [`WebError`] can fall into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde-1",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum WebErrorKind {
+    /// The server responded with `404 Not Found`.
+    NotFound,
+    /// The server responded with `410 Gone`.
+    Gone,
+    /// The server responded with some other `4xx` status code.
+    ClientError,
+    /// The server responded with a `5xx` status code.
+    ServerError,
+    /// The host name couldn't be resolved.
+    Dns,
+    /// We couldn't establish a secure connection (e.g. because of an invalid
+    /// certificate).
+    Tls,
+    /// The server refused the connection.
+    ConnectionRefused,
+    /// We couldn't connect to the server for some other reason.
+    Connect,
+    /// The request timed out.
+    Timeout,
+    /// We gave up after too many redirects, probably because of a redirect
+    /// loop.
+    TooManyRedirects,
+    /// Something else went wrong.
+    Other,
+}
+
+impl WebErrorKind {
+    fn of(error: &reqwest::Error) -> Self {
+        if let Some(status) = error.status() {
+            return match status {
+                StatusCode::NOT_FOUND => WebErrorKind::NotFound,
+                StatusCode::GONE => WebErrorKind::Gone,
+                _ if status.is_client_error() => WebErrorKind::ClientError,
+                _ if status.is_server_error() => WebErrorKind::ServerError,
+                _ => WebErrorKind::Other,
+            };
+        }
+
+        if error.is_timeout() {
+            WebErrorKind::Timeout
+        } else if error.is_redirect() {
+            WebErrorKind::TooManyRedirects
+        } else if error.is_connect() {
+            WebErrorKind::of_connect_error(error)
+        } else {
+            WebErrorKind::Other
+        }
+    }
+
+    /// Work out why we couldn't connect by walking the chain of errors.
+    ///
+    /// Refused connections and TLS failures are recognised by their
+    /// [`io::ErrorKind`] and [`native_tls::Error`] type. The HTTP client
+    /// doesn't expose a type for DNS failures, so those are a best-effort
+    /// guess based on the message `hyper` uses, and anything we can't
+    /// recognise is reported as [`WebErrorKind::Connect`].
+    fn of_connect_error(error: &reqwest::Error) -> Self {
+        let mut source: Option<&(dyn Error + 'static)> = Some(error);
+
+        while let Some(err) = source {
+            if err.is::<native_tls::Error>() {
+                return WebErrorKind::Tls;
+            }
+
+            if let Some(io_error) = err.downcast_ref::<io::Error>() {
+                if io_error.kind() == io::ErrorKind::ConnectionRefused {
+                    return WebErrorKind::ConnectionRefused;
+                }
+            }
+
+            // hyper wraps resolver errors in a private type
+            if err.to_string().starts_with("dns error") {
+                return WebErrorKind::Dns;
+            }
+
+            source = err.source();
+        }
+
+        WebErrorKind::Connect
+    }
+
+    /// Is this sort of error likely to go away if we try again later?
+    pub fn is_temporary(self) -> bool {
+        matches!(
+            self,
+            WebErrorKind::ServerError
+                | WebErrorKind::Connect
+                | WebErrorKind::ConnectionRefused
+                | WebErrorKind::Timeout
+        )
+    }
+}
+
+impl Display for WebErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            WebErrorKind::NotFound => "The page doesn't exist",
+            WebErrorKind::Gone => "The page has been removed",
+            WebErrorKind::ClientError => "The server rejected the request",
+            WebErrorKind::ServerError => "The server encountered an error",
+            WebErrorKind::Dns => "Unable to resolve the host name",
+            WebErrorKind::Tls => "Unable to establish a secure connection",
+            WebErrorKind::ConnectionRefused => "The connection was refused",
+            WebErrorKind::Connect => "Unable to connect to the server",
+            WebErrorKind::Timeout => "The request timed out",
+            WebErrorKind::TooManyRedirects => "There were too many redirects",
+            WebErrorKind::Other => "The web client encountered an error",
+        };
+
+        f.write_str(description)
+    }
+}